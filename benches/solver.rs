@@ -0,0 +1,95 @@
+use std::hint::black_box;
+use std::rc::Rc;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use lhopital::{DerivativeCache, Expression, lhopital_solve};
+
+// `(x - 1)^n` expanded by the binomial theorem, so that it vanishes to order `n` at 1
+// and takes `n` applications of the rule against `(x - 1)^n`.
+fn expanded_power(n: usize) -> Expression {
+    let mut coefficients = vec![0.0; n + 1];
+    let mut binomial = 1.0;
+    for (k, coefficient) in coefficients.iter_mut().enumerate() {
+        let sign = if (n - k).is_multiple_of(2) { 1.0 } else { -1.0 };
+        *coefficient = sign * binomial;
+        binomial = binomial * (n - k) as f64 / (k + 1) as f64;
+    }
+    Expression::from_polynomial(&coefficients)
+}
+
+// A tree that doubles in size with each level: `f(k+1) = sin(f(k)) * (f(k) + k)`.
+fn nested(levels: usize) -> Expression {
+    let mut expr = Expression::var();
+    for k in 0..levels {
+        expr = Expression::Sin(Rc::new(expr.clone())) * (expr + k as f64);
+    }
+    expr
+}
+
+// A degenerate tree leaning to the left: `((x + 1) + 1) + ...` with `depth` sums.
+fn chain(depth: usize) -> Expression {
+    let mut expr = Expression::var();
+    for _ in 0..depth {
+        expr = expr + 1.0;
+    }
+    expr
+}
+
+fn differentiate(c: &mut Criterion) {
+    let polynomial = expanded_power(12);
+    c.bench_function("differentiate polynomial", |b| {
+        b.iter(|| black_box(&polynomial).differentiate())
+    });
+    let tree = nested(10);
+    c.bench_function("differentiate nested", |b| {
+        b.iter(|| black_box(&tree).differentiate())
+    });
+    // Each level holds the one below twice, but hashing a subtree to look it up costs
+    // about as much as differentiating it again, so a fresh cache only breaks even. The
+    // gain is in differentiating the same tree again, as across iterations of the rule,
+    // which a warm cache answers from a single lookup.
+    c.bench_function("differentiate_cached nested", |b| {
+        b.iter(|| black_box(&tree).differentiate_cached(&mut DerivativeCache::new()))
+    });
+    let mut cache = DerivativeCache::new();
+    c.bench_function("differentiate_cached nested, warm cache", |b| {
+        b.iter(|| black_box(&tree).differentiate_cached(&mut cache))
+    });
+}
+
+fn evaluate(c: &mut Criterion) {
+    let polynomial = expanded_power(12);
+    c.bench_function("evaluate polynomial", |b| {
+        b.iter(|| black_box(&polynomial).evaluate(black_box(1.5)))
+    });
+    let tree = nested(10);
+    c.bench_function("evaluate nested", |b| {
+        b.iter(|| black_box(&tree).evaluate(black_box(0.5)))
+    });
+    c.bench_function("evaluate_iter nested", |b| {
+        b.iter(|| black_box(&tree).evaluate_iter(black_box(0.5)))
+    });
+    let chain = chain(1000);
+    c.bench_function("evaluate chain", |b| {
+        b.iter(|| black_box(&chain).evaluate(black_box(0.5)))
+    });
+    c.bench_function("evaluate_iter chain", |b| {
+        b.iter(|| black_box(&chain).evaluate_iter(black_box(0.5)))
+    });
+}
+
+fn solve(c: &mut Criterion) {
+    let numerator = Expression::parse("1 - cos(x)").unwrap();
+    let denominator = Expression::parse("x^2").unwrap();
+    c.bench_function("solve (1 - cos(x)) / x^2", |b| {
+        b.iter(|| lhopital_solve(black_box(&numerator), black_box(&denominator), 0.0, 10))
+    });
+    let numerator = expanded_power(6);
+    let denominator = Expression::parse("(x - 1)^6").unwrap();
+    c.bench_function("solve expanded (x - 1)^6 / (x - 1)^6", |b| {
+        b.iter(|| lhopital_solve(black_box(&numerator), black_box(&denominator), 1.0, 10))
+    });
+}
+
+criterion_group!(benches, differentiate, evaluate, solve);
+criterion_main!(benches);
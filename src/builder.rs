@@ -0,0 +1,278 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::{Condition, Expression, ParseError};
+
+impl Expression {
+    /// The variable `x`.
+    pub fn var() -> Self {
+        Expression::Variable
+    }
+
+    /// The constant `value`.
+    pub fn c(value: f64) -> Self {
+        Expression::Constant(value)
+    }
+
+    /// The constant `0`.
+    pub fn zero() -> Self {
+        Expression::Constant(0.0)
+    }
+
+    /// The constant `1`.
+    pub fn one() -> Self {
+        Expression::Constant(1.0)
+    }
+
+    /// `self^n`.
+    pub fn pow(self, n: f64) -> Self {
+        Expression::Power(Rc::new(self), n)
+    }
+
+    /// The piecewise expression taking the first of `branches` whose condition holds,
+    /// e.g. `-x` for `x < 0` and `x` otherwise.
+    pub fn piecewise(branches: impl IntoIterator<Item = (Condition, Expression)>) -> Self {
+        Expression::Piecewise(
+            branches
+                .into_iter()
+                .map(|(condition, branch)| (condition, Rc::new(branch)))
+                .collect(),
+        )
+    }
+
+    /// The polynomial with the given coefficients, lowest degree first, written from
+    /// the highest degree down. Zero terms are skipped, so `&[-4.0, 0.0, 1.0]` gives
+    /// `x^2 - 4`.
+    pub fn from_polynomial(coefficients: &[f64]) -> Self {
+        let term = |degree: usize, coefficient: f64| monomial(degree as f64, coefficient);
+        let mut terms = coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|&(_, &coefficient)| coefficient != 0.0);
+        let Some((degree, &coefficient)) = terms.next() else {
+            return Expression::zero();
+        };
+        terms.fold(
+            term(degree, coefficient),
+            |polynomial, (degree, &coefficient)| {
+                if coefficient < 0.0 {
+                    polynomial - term(degree, -coefficient)
+                } else {
+                    polynomial + term(degree, coefficient)
+                }
+            },
+        )
+    }
+}
+
+// `coefficient * x^degree`, written without a unit coefficient or exponent.
+pub(crate) fn monomial(degree: f64, coefficient: f64) -> Expression {
+    let power = if degree == 0.0 {
+        return Expression::Constant(coefficient);
+    } else if degree == 1.0 {
+        Expression::Variable
+    } else {
+        Expression::Variable.pow(degree)
+    };
+    if coefficient == 1.0 {
+        power
+    } else if coefficient == -1.0 {
+        -power
+    } else {
+        Expression::Constant(coefficient) * power
+    }
+}
+
+/// The constant `0`, so an expression can be moved out of a slot with `std::mem::take`.
+impl Default for Expression {
+    fn default() -> Self {
+        Expression::zero()
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Expression::Constant(value)
+    }
+}
+
+/// Parses with [`Expression::parse`]. Parsing can fail, so strings convert with
+/// `str::parse` or `TryFrom` rather than `From`.
+impl FromStr for Expression {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expression::parse(s)
+    }
+}
+
+impl TryFrom<&str> for Expression {
+    type Error = ParseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Expression::parse(s)
+    }
+}
+
+// The arithmetic operators build the corresponding node without simplifying it, so
+// `a + b` is exactly `Sum(a, b)`. Borrowed operands are cloned and `f64` operands
+// become constants.
+macro_rules! binary_operator {
+    ($trait:ident, $method:ident, $variant:ident) => {
+        impl $trait for Expression {
+            type Output = Expression;
+
+            fn $method(self, other: Expression) -> Expression {
+                Expression::$variant(Rc::new(self), Rc::new(other))
+            }
+        }
+
+        impl $trait<&Expression> for Expression {
+            type Output = Expression;
+
+            fn $method(self, other: &Expression) -> Expression {
+                self.$method(other.clone())
+            }
+        }
+
+        impl $trait<Expression> for &Expression {
+            type Output = Expression;
+
+            fn $method(self, other: Expression) -> Expression {
+                self.clone().$method(other)
+            }
+        }
+
+        impl $trait for &Expression {
+            type Output = Expression;
+
+            fn $method(self, other: &Expression) -> Expression {
+                self.clone().$method(other.clone())
+            }
+        }
+
+        impl $trait<f64> for Expression {
+            type Output = Expression;
+
+            fn $method(self, other: f64) -> Expression {
+                self.$method(Expression::from(other))
+            }
+        }
+
+        impl $trait<f64> for &Expression {
+            type Output = Expression;
+
+            fn $method(self, other: f64) -> Expression {
+                self.clone().$method(Expression::from(other))
+            }
+        }
+
+        impl $trait<Expression> for f64 {
+            type Output = Expression;
+
+            fn $method(self, other: Expression) -> Expression {
+                Expression::from(self).$method(other)
+            }
+        }
+
+        impl $trait<&Expression> for f64 {
+            type Output = Expression;
+
+            fn $method(self, other: &Expression) -> Expression {
+                Expression::from(self).$method(other.clone())
+            }
+        }
+    };
+}
+
+binary_operator!(Add, add, Sum);
+binary_operator!(Sub, sub, Difference);
+binary_operator!(Mul, mul, Product);
+binary_operator!(Div, div, Quotient);
+
+impl Neg for Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        Expression::Negate(Rc::new(self))
+    }
+}
+
+impl Neg for &Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        -self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder() {
+        let expr = Expression::var().pow(2.0) - Expression::c(4.0);
+        assert_eq!(expr, Expression::parse("x^2 - 4").unwrap());
+        assert_eq!(Expression::zero(), Expression::Constant(0.0));
+        assert_eq!(Expression::one(), Expression::Constant(1.0));
+        let abs = Expression::piecewise([
+            (Condition::Less(0.0), -Expression::var()),
+            (Condition::GreaterOrEqual(0.0), Expression::var()),
+        ]);
+        assert_eq!(abs.evaluate(-3.0), 3.0);
+        assert_eq!(abs.evaluate(2.0), 2.0);
+    }
+
+    #[test]
+    fn operators_build_the_same_trees() {
+        let x = Expression::var();
+        let c = |value| Rc::new(Expression::Constant(value));
+        assert_eq!(
+            (&x + 1.0) * (&x - 2.0),
+            Expression::Product(
+                Rc::new(Expression::Sum(Rc::new(Expression::Variable), c(1.0))),
+                Rc::new(Expression::Difference(
+                    Rc::new(Expression::Variable),
+                    c(2.0)
+                )),
+            )
+        );
+        assert_eq!(
+            2.0 / -x.clone(),
+            Expression::Quotient(c(2.0), Rc::new(Expression::Negate(Rc::new(x))))
+        );
+    }
+
+    #[test]
+    fn from_polynomial() {
+        let polynomial = Expression::from_polynomial(&[-4.0, 0.0, 1.0]);
+        assert_eq!(polynomial.to_string(), "x^2 - 4");
+        let cubic = Expression::from_polynomial(&[1.0, -2.0, 0.0, 3.0]);
+        for x in [-1.5, 0.0, 2.0] {
+            assert_eq!(cubic.evaluate(x), 1.0 - 2.0 * x + 3.0 * x * x * x);
+        }
+        assert_eq!(Expression::from_polynomial(&[]), Expression::zero());
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Expression::from(2.5), Expression::Constant(2.5));
+        let one: Expression = 1.0.into();
+        assert_eq!(one + Expression::var(), Expression::parse("1 + x").unwrap());
+        assert_eq!(
+            Expression::try_from("x^2 - 4"),
+            Ok(Expression::var().pow(2.0) - 4.0)
+        );
+        assert_eq!("sin(x)".parse::<Expression>(), Expression::parse("sin(x)"));
+        assert!(Expression::try_from("x +").is_err());
+    }
+
+    #[test]
+    fn default_is_zero() {
+        assert_eq!(Expression::default().evaluate(5.0), 0.0);
+        assert_eq!(Expression::default(), Expression::zero());
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use crate::Expression;
+
+/// Remembers the derivatives of subexpressions so that repeated differentiation, such
+/// as across L'Hôpital iterations, reuses earlier results instead of rebuilding them.
+///
+/// Entries are keyed on the subexpression and the variable it was differentiated with
+/// respect to.
+#[derive(Debug, Clone, Default)]
+pub struct DerivativeCache {
+    entries: HashMap<(char, Expression), Expression>,
+}
+
+impl DerivativeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached derivatives.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn get(&self, var: char, expr: &Expression) -> Option<&Expression> {
+        // Cloning only bumps the reference counts of the children.
+        self.entries.get(&(var, expr.clone()))
+    }
+
+    pub(crate) fn insert(&mut self, var: char, expr: Expression, derivative: Expression) {
+        self.entries.insert((var, expr), derivative);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_derivatives_match() {
+        let mut cache = DerivativeCache::new();
+        for input in ["sin(x^2) * exp(x)", "(x^2 - 4) / (x - 2)", "ln(sin(x^2))"] {
+            let expr = Expression::parse(input).unwrap();
+            let derivative = expr.differentiate().unwrap();
+            assert_eq!(expr.differentiate_cached(&mut cache).unwrap(), derivative);
+            // A second lookup is served from the cache.
+            assert_eq!(expr.differentiate_cached(&mut cache).unwrap(), derivative);
+        }
+        assert!(!cache.is_empty());
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -0,0 +1,97 @@
+use num_complex::Complex64;
+
+use crate::Expression;
+
+impl Expression {
+    /// Evaluates the expression at the complex point `z`, taking the principal branch
+    /// of every multivalued function.
+    ///
+    /// Integer powers are exact, but other powers and roots use the principal branch,
+    /// so unlike [`Expression::evaluate`] an odd root of a negative number is not
+    /// real. Variables other than `x` and division by zero evaluate to NaN.
+    pub fn evaluate_complex(&self, z: Complex64) -> Complex64 {
+        let nan = Complex64::new(f64::NAN, f64::NAN);
+        match self {
+            Expression::Constant(c) => Complex64::new(*c, 0.0),
+            Expression::Pi => Complex64::new(std::f64::consts::PI, 0.0),
+            Expression::E => Complex64::new(std::f64::consts::E, 0.0),
+            Expression::Variable => z,
+            Expression::Symbol(_) => nan,
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => Complex64::new(*n as f64 / *d as f64, 0.0),
+            Expression::Sum(a, b) => a.evaluate_complex(z) + b.evaluate_complex(z),
+            Expression::Difference(a, b) => a.evaluate_complex(z) - b.evaluate_complex(z),
+            Expression::Negate(a) => -a.evaluate_complex(z),
+            Expression::Product(a, b) => a.evaluate_complex(z) * b.evaluate_complex(z),
+            Expression::Quotient(a, b) => {
+                let den = b.evaluate_complex(z);
+                if den == Complex64::ZERO {
+                    nan
+                } else {
+                    a.evaluate_complex(z) / den
+                }
+            }
+            Expression::Power(base, exp) if exp.fract() == 0.0 && exp.abs() <= i32::MAX as f64 => {
+                base.evaluate_complex(z).powi(*exp as i32)
+            }
+            Expression::Power(base, exp) => base.evaluate_complex(z).powf(*exp),
+            Expression::PowerExpr(base, exp) => {
+                base.evaluate_complex(z).powc(exp.evaluate_complex(z))
+            }
+            Expression::Root(a, n) => a.evaluate_complex(z).powf(1.0 / n),
+            Expression::Sin(a) => a.evaluate_complex(z).sin(),
+            Expression::Cos(a) => a.evaluate_complex(z).cos(),
+            Expression::Tan(a) => a.evaluate_complex(z).tan(),
+            Expression::Reciprocal(a) => {
+                let den = a.evaluate_complex(z);
+                if den == Complex64::ZERO {
+                    nan
+                } else {
+                    den.inv()
+                }
+            }
+            Expression::Sec(a) => a.evaluate_complex(z).cos().inv(),
+            Expression::Csc(a) => a.evaluate_complex(z).sin().inv(),
+            Expression::Cot(a) => a.evaluate_complex(z).tan().inv(),
+            Expression::Atan(a) => a.evaluate_complex(z).atan(),
+            Expression::Sinh(a) => a.evaluate_complex(z).sinh(),
+            Expression::Cosh(a) => a.evaluate_complex(z).cosh(),
+            Expression::Abs(a) => Complex64::new(a.evaluate_complex(z).norm(), 0.0),
+            // The unit number in the direction of the argument, which is undefined at 0.
+            Expression::Sign(a) => {
+                let arg = a.evaluate_complex(z);
+                if arg == Complex64::ZERO {
+                    nan
+                } else {
+                    arg / arg.norm()
+                }
+            }
+            Expression::Exp(a) => a.evaluate_complex(z).exp(),
+            Expression::Ln(a) => a.evaluate_complex(z).ln(),
+            Expression::Derivative(a) => a
+                .differentiate()
+                .map_or(nan, |derivative| derivative.evaluate_complex(z)),
+            Expression::Log(base, a) => a.evaluate_complex(z).log(*base),
+            // The conditions only order real numbers.
+            Expression::Piecewise(branches) if z.im == 0.0 => branches
+                .iter()
+                .find(|(condition, _)| condition.holds(z.re))
+                .map_or(nan, |(_, branch)| branch.evaluate_complex(z)),
+            Expression::Piecewise(_) => nan,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_at_i() {
+        let expr = Expression::parse("x^2 + 1").unwrap();
+        assert_eq!(
+            expr.evaluate_complex(Complex64::i()),
+            Complex64::new(0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    DEFAULT_MAX_DEPTH, DEFAULT_MAX_ITERATIONS, DEFAULT_MAX_MAGNITUDE, DEFAULT_TOLERANCE,
+    NUMERIC_EPSILON,
+};
+
+/// Settings for [`lhopital_solve_with`](crate::lhopital_solve_with). Start from
+/// [`SolveConfig::default`] and override what you need, e.g.
+/// `SolveConfig::default().max_iterations(50).tolerance(1e-12)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveConfig {
+    /// How many times L'Hôpital's Rule may be applied before giving up. With `0` the
+    /// quotient is only evaluated as given.
+    pub max_iterations: u32,
+    /// Magnitude below which a value counts as zero when detecting `0/0`.
+    pub tolerance: f64,
+    /// Fraction of an expression's magnitude just beside a finite limit point below
+    /// which its value at the point also counts as zero, so that rounding error in
+    /// large-scale expressions is not mistaken for a nonzero value. With `0`, the
+    /// default, only the absolute `tolerance` applies.
+    pub relative_tolerance: f64,
+    /// Print every iteration to stdout as the solver runs.
+    pub verbose: bool,
+    /// Decimal places shown for the values printed when `verbose` is set, or `None`
+    /// for as many as each value needs.
+    pub precision: Option<usize>,
+    /// Cross-check the symbolic result against a numeric estimate, reported in
+    /// [`LhopitalResult::numeric_mismatch`](crate::LhopitalResult::numeric_mismatch).
+    pub numeric_check: bool,
+    /// When an expression has no symbolic derivative, apply the rule with central
+    /// differences instead, estimating the limit of `f'/g'` beside the point.
+    pub numeric_derivatives: bool,
+    /// Before differentiating polynomials that both vanish at a finite point, divide
+    /// their common factor `x - at` out instead.
+    pub cancel_factors: bool,
+    /// Offset from the limit point used when sampling numerically.
+    pub epsilon: f64,
+    /// Nesting depth past which an expression is rejected.
+    pub max_depth: usize,
+    /// Magnitude past which a sampled value counts as having overflowed. When both the
+    /// numerator and the denominator overflow, the solver stops with
+    /// [`LhopitalError::Overflow`](crate::LhopitalError::Overflow) instead of
+    /// differentiating. An infinity at a finite point is a genuine singularity, like
+    /// `ln(0)`, and only counts at a limit at infinity, where it comes from sampling at
+    /// the infinity proxy.
+    pub max_magnitude: f64,
+}
+
+impl Default for SolveConfig {
+    fn default() -> Self {
+        SolveConfig {
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            tolerance: DEFAULT_TOLERANCE,
+            relative_tolerance: 0.0,
+            verbose: false,
+            precision: None,
+            numeric_check: true,
+            numeric_derivatives: false,
+            cancel_factors: true,
+            epsilon: NUMERIC_EPSILON,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_magnitude: DEFAULT_MAX_MAGNITUDE,
+        }
+    }
+}
+
+impl SolveConfig {
+    pub fn max_iterations(mut self, max_iterations: u32) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn relative_tolerance(mut self, relative_tolerance: f64) -> Self {
+        self.relative_tolerance = relative_tolerance;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    pub fn numeric_check(mut self, numeric_check: bool) -> Self {
+        self.numeric_check = numeric_check;
+        self
+    }
+
+    pub fn numeric_derivatives(mut self, numeric_derivatives: bool) -> Self {
+        self.numeric_derivatives = numeric_derivatives;
+        self
+    }
+
+    pub fn cancel_factors(mut self, cancel_factors: bool) -> Self {
+        self.cancel_factors = cancel_factors;
+        self
+    }
+
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_magnitude(mut self, max_magnitude: f64) -> Self {
+        self.max_magnitude = max_magnitude;
+        self
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::io::Write;
+
+use crate::solver::{solve_as_given, solve_expression};
+use crate::{Expression, LhopitalError, LhopitalResult, LimitPoint, SolveConfig};
+
+/// A function of `x` that can be evaluated and differentiated, which is all
+/// [`lhopital_solve`](crate::lhopital_solve) and the other solvers need to apply
+/// L'Hôpital's Rule to it.
+///
+/// Implement this for a type of your own to solve limits of functions that
+/// [`Expression`] cannot represent, such as a special function from your domain. The
+/// solver keeps the quotients it evaluates in [`LhopitalResult::iterations`], compares
+/// them to notice derivatives that cycle, and prints them in its verbose trace, hence
+/// the supertraits.
+pub trait Differentiable: Clone + PartialEq + fmt::Display {
+    /// The value at `x`, or NaN where the function is undefined.
+    fn evaluate(&self, x: f64) -> f64;
+
+    /// The derivative with respect to `x`.
+    fn differentiate(&self) -> Result<Self, LhopitalError>;
+
+    /// Solves the limit of `numerator / denominator` as `x -> at` for the solvers,
+    /// with `at` already checked to be a valid point.
+    ///
+    /// The default applies L'Hôpital's Rule to the quotient as it stands, which is
+    /// rarely worth changing. [`Expression`] replaces it to rework the quotient first,
+    /// e.g. cancelling common factors and rewriting the `0·∞` and `∞-∞` forms, which
+    /// needs the structure of the tree.
+    fn solve_limit(
+        numerator: &Self,
+        denominator: &Self,
+        at: LimitPoint,
+        config: &SolveConfig,
+        out: &mut dyn Write,
+    ) -> Result<LhopitalResult<Self>, LhopitalError> {
+        solve_as_given(numerator, denominator, at, config, out)
+    }
+}
+
+impl Differentiable for Expression {
+    fn evaluate(&self, x: f64) -> f64 {
+        Expression::evaluate(self, x)
+    }
+
+    fn differentiate(&self) -> Result<Self, LhopitalError> {
+        Expression::differentiate(self).map(|derivative| derivative.simplify())
+    }
+
+    fn solve_limit(
+        numerator: &Self,
+        denominator: &Self,
+        at: LimitPoint,
+        config: &SolveConfig,
+        out: &mut dyn Write,
+    ) -> Result<LhopitalResult, LhopitalError> {
+        solve_expression(numerator, denominator, at, config, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lhopital_solve, lhopital_solve_traced, solve_with_trace_to_writer};
+
+    // A polynomial by its coefficients, lowest degree first.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Polynomial(Vec<f64>);
+
+    impl fmt::Display for Polynomial {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let terms: Vec<String> = self
+                .0
+                .iter()
+                .enumerate()
+                .map(|(degree, c)| format!("{}x^{}", c, degree))
+                .collect();
+            write!(f, "{}", terms.join(" + "))
+        }
+    }
+
+    impl Differentiable for Polynomial {
+        fn evaluate(&self, x: f64) -> f64 {
+            self.0.iter().rev().fold(0.0, |value, c| value * x + c)
+        }
+
+        fn differentiate(&self) -> Result<Self, LhopitalError> {
+            Ok(Polynomial(
+                self.0
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .map(|(degree, c)| degree as f64 * c)
+                    .collect(),
+            ))
+        }
+    }
+
+    fn p(coefficients: &[f64]) -> Polynomial {
+        Polynomial(coefficients.to_vec())
+    }
+
+    #[test]
+    fn solves_a_custom_type() {
+        // (x^3 - 1) / (x - 1) at 1 is 3, while x^2 / x^3 at 0 has a pole.
+        let result = lhopital_solve_traced(&p(&[-1.0, 0.0, 0.0, 1.0]), &p(&[-1.0, 1.0]), 1.0, 10);
+        let result = result.unwrap();
+        assert_eq!(result.value, 3.0);
+        assert_eq!(result.iterations_applied, 1);
+        assert_eq!(result.final_denominator, p(&[1.0]));
+        let limit = lhopital_solve(&p(&[0.0, 0.0, 1.0]), &p(&[0.0, 0.0, 0.0, 1.0]), 0.0, 10);
+        assert!(matches!(limit, Err(LhopitalError::InfiniteLimit { .. })));
+        // Equal parts give 1 without differentiating.
+        let result = lhopital_solve_traced(&p(&[0.0, 1.0]), &p(&[0.0, 1.0]), 0.0, 0).unwrap();
+        assert_eq!((result.value, result.iterations_applied), (1.0, 0));
+    }
+
+    #[test]
+    fn solves_a_custom_type_at_infinity() {
+        let (square, cube) = (p(&[0.0, 0.0, 1.0]), p(&[0.0, 0.0, 0.0, 1.0]));
+        let half = lhopital_solve(
+            &p(&[1.0, 0.0, 1.0]),
+            &p(&[0.0, 0.0, 2.0]),
+            f64::INFINITY,
+            10,
+        );
+        assert_eq!(half, Ok(0.5));
+        assert_eq!(lhopital_solve(&square, &cube, f64::INFINITY, 10), Ok(0.0));
+        assert_eq!(
+            lhopital_solve(&cube, &square, f64::INFINITY, 10),
+            Ok(f64::INFINITY)
+        );
+        assert_eq!(
+            lhopital_solve(&cube, &square, f64::NEG_INFINITY, 10),
+            Ok(f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn custom_type_agrees_with_expression() {
+        let quotients: [(&[f64], &[f64]); 4] = [
+            (&[-4.0, 0.0, 1.0], &[-2.0, 1.0]),
+            (&[0.0, 0.0, 3.0], &[0.0, 0.0, 1.0, 1.0]),
+            (&[1.0, 2.0, 1.0], &[2.0, 0.0, 5.0]),
+            (&[0.0, 1.0, 0.0, -1.0], &[0.0, 0.0, 2.0]),
+        ];
+        for (num, den) in quotients {
+            for at in [2.0, 0.0, f64::INFINITY, f64::NEG_INFINITY] {
+                let custom = lhopital_solve(&p(num), &p(den), at, 10);
+                let expression = lhopital_solve(
+                    &Expression::from_polynomial(num),
+                    &Expression::from_polynomial(den),
+                    at,
+                    10,
+                );
+                assert_eq!(
+                    format!("{:?}", custom),
+                    format!("{:?}", expression),
+                    "{:?} / {:?} at {}",
+                    num,
+                    den,
+                    at
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn traces_a_custom_type() {
+        let config = SolveConfig::default().verbose(true);
+        let mut trace = Vec::new();
+        solve_with_trace_to_writer(&p(&[0.0, 2.0]), &p(&[0.0, 1.0]), 0.0, &config, &mut trace)
+            .unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        assert!(
+            trace.starts_with("Iteration 0: (0x^0 + 2x^1) / (0x^0 + 1x^1) at x = 0: 0 / 0\n"),
+            "{}",
+            trace
+        );
+    }
+}
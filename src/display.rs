@@ -0,0 +1,216 @@
+use std::fmt;
+
+use crate::{Condition, Expression};
+
+// Binding strength of each node when rendered infix; a child is parenthesized
+// whenever it binds more loosely than its position requires.
+pub(crate) const SUM: u8 = 1;
+pub(crate) const PRODUCT: u8 = 2;
+pub(crate) const UNARY: u8 = 3;
+pub(crate) const ATOM: u8 = 4;
+
+impl Expression {
+    /// Renders the expression like `Display`, but with the variable written as `var`,
+    /// so `x^2 - 4` becomes `t^2 - 4` for `var = "t"`.
+    pub fn format_with(&self, var: &str) -> String {
+        Named { expr: self, var }.to_string()
+    }
+
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expression::Constant(c) if *c < 0.0 => UNARY,
+            Expression::Negate(_) => UNARY,
+            // Shown as `n/d`, like the quotient it stands for.
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => PRODUCT,
+            Expression::Sum(..) | Expression::Difference(..) => SUM,
+            Expression::Product(..) | Expression::Quotient(..) => PRODUCT,
+            // A power is shown as `base^n` and only ever needs parentheses as a base itself.
+            Expression::Power(..) | Expression::PowerExpr(..) => UNARY,
+            Expression::Constant(_)
+            | Expression::Pi
+            | Expression::E
+            | Expression::Variable
+            | Expression::Symbol(_)
+            | Expression::Sin(_)
+            | Expression::Cos(_)
+            | Expression::Tan(_)
+            | Expression::Reciprocal(_)
+            | Expression::Sec(_)
+            | Expression::Csc(_)
+            | Expression::Cot(_)
+            | Expression::Atan(_)
+            | Expression::Sinh(_)
+            | Expression::Cosh(_)
+            | Expression::Abs(_)
+            | Expression::Sign(_)
+            | Expression::Exp(_)
+            | Expression::Ln(_)
+            | Expression::Derivative(_)
+            | Expression::Log(..)
+            | Expression::Root(..)
+            | Expression::Piecewise(_) => ATOM,
+        }
+    }
+}
+
+// An expression rendered with `var` in place of `x`.
+struct Named<'a> {
+    expr: &'a Expression,
+    var: &'a str,
+}
+
+impl<'a> Named<'a> {
+    fn child(&self, expr: &'a Expression) -> Named<'a> {
+        Named {
+            expr,
+            var: self.var,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.expr.precedence() < min_precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for Named<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expr {
+            Expression::Constant(c) => write!(f, "{}", c),
+            Expression::Pi => write!(f, "π"),
+            Expression::E => write!(f, "e"),
+            Expression::Variable => write!(f, "{}", self.var),
+            Expression::Symbol(name) => write!(f, "{}", name),
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => write!(f, "{}/{}", n, d),
+            Expression::Sum(a, b) => {
+                self.child(a).fmt_operand(f, SUM)?;
+                write!(f, " + ")?;
+                self.child(b).fmt_operand(f, SUM)
+            }
+            Expression::Difference(a, b) => {
+                self.child(a).fmt_operand(f, SUM)?;
+                write!(f, " - ")?;
+                self.child(b).fmt_operand(f, PRODUCT)
+            }
+            Expression::Negate(a) => {
+                write!(f, "-")?;
+                // Parenthesize nested negations so they don't render as `--x`.
+                match **a {
+                    Expression::Negate(_) => write!(f, "({})", self.child(a)),
+                    Expression::Constant(c) if c < 0.0 => write!(f, "({})", self.child(a)),
+                    _ => self.child(a).fmt_operand(f, UNARY),
+                }
+            }
+            Expression::Product(a, b) => {
+                self.child(a).fmt_operand(f, PRODUCT)?;
+                write!(f, " * ")?;
+                self.child(b).fmt_operand(f, PRODUCT)
+            }
+            Expression::Quotient(a, b) => {
+                self.child(a).fmt_operand(f, PRODUCT)?;
+                write!(f, " / ")?;
+                self.child(b).fmt_operand(f, UNARY)
+            }
+            Expression::Power(base, exp) => {
+                self.child(base).fmt_operand(f, ATOM)?;
+                write!(f, "^{}", exp)
+            }
+            Expression::PowerExpr(base, exp) => {
+                self.child(base).fmt_operand(f, ATOM)?;
+                write!(f, "^")?;
+                self.child(exp).fmt_operand(f, ATOM)
+            }
+            Expression::Root(a, n) if *n == 2.0 => write!(f, "sqrt({})", self.child(a)),
+            Expression::Root(a, n) => write!(f, "root({}, {})", self.child(a), n),
+            Expression::Sin(a) => write!(f, "sin({})", self.child(a)),
+            Expression::Cos(a) => write!(f, "cos({})", self.child(a)),
+            Expression::Tan(a) => write!(f, "tan({})", self.child(a)),
+            Expression::Reciprocal(a) => write!(f, "recip({})", self.child(a)),
+            Expression::Sec(a) => write!(f, "sec({})", self.child(a)),
+            Expression::Csc(a) => write!(f, "csc({})", self.child(a)),
+            Expression::Cot(a) => write!(f, "cot({})", self.child(a)),
+            Expression::Atan(a) => write!(f, "atan({})", self.child(a)),
+            Expression::Sinh(a) => write!(f, "sinh({})", self.child(a)),
+            Expression::Cosh(a) => write!(f, "cosh({})", self.child(a)),
+            Expression::Abs(a) => write!(f, "abs({})", self.child(a)),
+            Expression::Sign(a) => write!(f, "sign({})", self.child(a)),
+            Expression::Exp(a) => write!(f, "exp({})", self.child(a)),
+            Expression::Ln(a) => write!(f, "ln({})", self.child(a)),
+            Expression::Derivative(a) => write!(f, "d/dx({})", self.child(a)),
+            Expression::Log(base, a) if *base == 2.0 => write!(f, "log2({})", self.child(a)),
+            Expression::Log(base, a) if *base == 10.0 => write!(f, "log10({})", self.child(a)),
+            Expression::Log(base, a) => write!(f, "log({}, {})", base, self.child(a)),
+            Expression::Piecewise(branches) => {
+                write!(f, "piecewise(")?;
+                for (i, (condition, branch)) in branches.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    let (operator, bound) = match *condition {
+                        Condition::Less(c) => ("<", c),
+                        Condition::LessOrEqual(c) => ("<=", c),
+                        Condition::Greater(c) => (">", c),
+                        Condition::GreaterOrEqual(c) => (">=", c),
+                    };
+                    write!(
+                        f,
+                        "{} if {} {} {}",
+                        self.child(branch),
+                        self.var,
+                        operator,
+                        bound
+                    )?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(
+            &Named {
+                expr: self,
+                var: "x",
+            },
+            f,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parenthesizes_by_precedence() {
+        let cases = [
+            ("x^2 - 4", "x^2 - 4"),
+            ("(x + 1) * x", "(x + 1) * x"),
+            ("(x^2 - 4) / (x - 2)", "(x^2 - 4) / (x - 2)"),
+            ("x - (x + 1)", "x - (x + 1)"),
+            ("x / (2 * x)", "x / (2 * x)"),
+            ("(x + 1)^2", "(x + 1)^2"),
+            ("-x^2", "-x^2"),
+            ("sin(x)^2 * cos(x)", "sin(x)^2 * cos(x)"),
+            ("((x))+(1)", "x + 1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(format!("{}", Expression::parse(input).unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn variable_names() {
+        let expr = Expression::parse("sin(x) / x + x^2").unwrap();
+        assert_eq!(expr.format_with("t"), "sin(t) / t + t^2");
+        assert_eq!(expr.format_with("θ"), "sin(θ) / θ + θ^2");
+        assert_eq!(expr.to_string(), "sin(x) / x + x^2");
+    }
+}
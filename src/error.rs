@@ -0,0 +1,194 @@
+use std::fmt;
+
+/// Errors produced while differentiating expressions or solving limits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LhopitalError {
+    /// The denominator vanishes while the numerator does not, and does so beside the
+    /// limit point too, so the quotient does not even tend to an infinity.
+    DivisionByZero,
+    /// No determinate form was reached within the allowed number of iterations.
+    MaxIterationsExceeded,
+    /// No differentiation rule exists for the named expression.
+    DifferentiationUnsupported(String),
+    /// No antiderivative rule covers the named expression.
+    IntegrationUnsupported(String),
+    /// The expression is nested deeper than the allowed limit.
+    TooDeep { limit: usize },
+    /// A power has an exponent larger than the allowed limit in magnitude.
+    ExponentTooLarge { limit: f64 },
+    /// The one-sided limits differ, so the two-sided limit does not exist.
+    LimitDoesNotExist { left: f64, right: f64 },
+    /// A nonzero numerator over a vanishing denominator, where the quotient tends to
+    /// infinities of opposite sign on the two sides of the point.
+    InfiniteLimit { left: f64, right: f64 },
+    /// The expression is undefined on every side the limit point is approached from,
+    /// e.g. a fractional power of a negative number.
+    Undefined,
+    /// The quotient is already determinate with this value, so L'Hôpital's Rule does
+    /// not apply.
+    Determinate(f64),
+    /// The named subexpression is NaN or infinite at the point it was evaluated at.
+    DomainError(String),
+    /// The limit point is NaN, or an infinity approached from beyond it.
+    InvalidLimitPoint(String),
+    /// The numerator and denominator both overflowed past
+    /// [`SolveConfig::max_magnitude`](crate::SolveConfig::max_magnitude) at the point,
+    /// so their derivatives would only overflow too.
+    Overflow,
+    /// A part of a problem given to [`solve_batch`](crate::solve_batch), named by its
+    /// s-expression, could not be read back on the thread solving it.
+    BatchTransfer(String),
+}
+
+impl fmt::Display for LhopitalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LhopitalError::DivisionByZero => write!(f, "Limit results in division by zero."),
+            LhopitalError::MaxIterationsExceeded => write!(
+                f,
+                "Exceeded max iterations, could not find a determinate form."
+            ),
+            LhopitalError::DifferentiationUnsupported(expr) => {
+                write!(f, "Differentiation rule not implemented for {}.", expr)
+            }
+            LhopitalError::IntegrationUnsupported(expr) => {
+                write!(f, "Cannot find an antiderivative of {}.", expr)
+            }
+            LhopitalError::TooDeep { limit } => {
+                write!(f, "Expression is nested more than {} levels deep.", limit)
+            }
+            LhopitalError::ExponentTooLarge { limit } => {
+                write!(
+                    f,
+                    "Expression has an exponent larger than {} in magnitude.",
+                    limit
+                )
+            }
+            LhopitalError::LimitDoesNotExist { left, right } => write!(
+                f,
+                "Limit does not exist: the left limit is {} but the right limit is {}.",
+                left, right
+            ),
+            LhopitalError::InfiniteLimit { left, right } => write!(
+                f,
+                "No finite limit: the quotient tends to {} from the left and {} from the right.",
+                left, right
+            ),
+            LhopitalError::Undefined => {
+                write!(f, "Expression is undefined near the limit point.")
+            }
+            LhopitalError::Determinate(value) => write!(
+                f,
+                "The quotient is determinate with value {}; L'Hôpital's Rule does not apply.",
+                value
+            ),
+            LhopitalError::DomainError(expr) => {
+                write!(f, "{} is undefined or infinite at this point.", expr)
+            }
+            LhopitalError::InvalidLimitPoint(point) => {
+                write!(f, "Cannot take a limit at {}.", point)
+            }
+            LhopitalError::Overflow => write!(
+                f,
+                "Both parts of the quotient overflow at the limit point, so no determinate form can be reached."
+            ),
+            LhopitalError::BatchTransfer(sexpr) => {
+                write!(f, "Could not pass {} to the thread solving it.", sexpr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LhopitalError {}
+
+/// Errors produced while parsing an expression from a string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A character that does not start any token, with its byte offset.
+    UnexpectedCharacter(char, usize),
+    /// A token that is not valid at this point, with its byte offset.
+    UnexpectedToken(String, usize),
+    /// The input ended while more was expected.
+    UnexpectedEnd,
+    /// A numeric literal that could not be parsed.
+    InvalidNumber(String),
+    /// A name that is neither the variable nor a known function.
+    UnknownIdentifier(String),
+    /// A root degree that depends on a variable.
+    NonConstantExponent,
+    /// A logarithm base that depends on a variable.
+    NonConstantBase,
+    /// A limit point that is not a number, an infinity or a one-sided number.
+    InvalidLimitPoint(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedCharacter(c, pos) => {
+                write!(f, "Unexpected character '{}' at position {}.", c, pos)
+            }
+            ParseError::UnexpectedToken(token, pos) => {
+                write!(f, "Unexpected '{}' at position {}.", token, pos)
+            }
+            ParseError::UnexpectedEnd => write!(f, "Unexpected end of input."),
+            ParseError::InvalidNumber(literal) => write!(f, "Invalid number '{}'.", literal),
+            ParseError::UnknownIdentifier(name) => write!(f, "Unknown identifier '{}'.", name),
+            ParseError::NonConstantExponent => write!(f, "Root degrees must be constant."),
+            ParseError::NonConstantBase => write!(f, "Logarithm bases must be constant."),
+            ParseError::InvalidLimitPoint(point) => write!(f, "Invalid limit point '{}'.", point),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_has_a_message() {
+        let errors = [
+            LhopitalError::DivisionByZero,
+            LhopitalError::MaxIterationsExceeded,
+            LhopitalError::DifferentiationUnsupported(String::from("sign(x)")),
+            LhopitalError::IntegrationUnsupported(String::from("sin(x)")),
+            LhopitalError::TooDeep { limit: 10 },
+            LhopitalError::ExponentTooLarge { limit: 50.0 },
+            LhopitalError::LimitDoesNotExist {
+                left: -1.0,
+                right: 1.0,
+            },
+            LhopitalError::InfiniteLimit {
+                left: f64::NEG_INFINITY,
+                right: f64::INFINITY,
+            },
+            LhopitalError::Undefined,
+            LhopitalError::Determinate(2.0),
+            LhopitalError::DomainError(String::from("ln(x)")),
+            LhopitalError::InvalidLimitPoint(String::from("NaN")),
+            LhopitalError::Overflow,
+            LhopitalError::BatchTransfer(String::from("(+ x 1)")),
+        ];
+        for error in &errors {
+            assert!(!error.to_string().is_empty(), "{:?}", error);
+        }
+        assert_eq!(
+            LhopitalError::DivisionByZero.to_string(),
+            "Limit results in division by zero."
+        );
+        assert_eq!(
+            LhopitalError::DifferentiationUnsupported(String::from("sign(x)")).to_string(),
+            "Differentiation rule not implemented for sign(x)."
+        );
+        assert_eq!(
+            LhopitalError::LimitDoesNotExist {
+                left: -1.0,
+                right: 1.0
+            }
+            .to_string(),
+            "Limit does not exist: the left limit is -1 but the right limit is 1."
+        );
+    }
+}
@@ -0,0 +1,2112 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+#[cfg(feature = "rational")]
+use crate::rational::{self, Op};
+use crate::{DerivativeCache, Direction, LhopitalError};
+
+/// Default nesting depth past which the solver refuses an expression, keeping the
+/// recursive evaluation and differentiation well clear of a stack overflow.
+pub const DEFAULT_MAX_DEPTH: usize = 1_000;
+
+/// A symbolic expression in `x`, optionally involving other named variables.
+///
+/// Equality is structural: `x + 1` and `1 + x` are different trees. Use
+/// [`Expression::approx_equal`] to compare expressions by value. Expressions are
+/// `Eq` and `Hash`, so they can key a `HashMap`, with one caveat: a tree containing a
+/// NaN constant is not equal even to itself, so it can be inserted but never looked
+/// up again.
+///
+/// Children are reference-counted, so cloning an expression or building on one of its
+/// subtrees shares nodes instead of copying them.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expression {
+    /// A numeric constant. Any `f64` can be stored and evaluates as itself, but the
+    /// solvers reject NaN and infinite constants with [`LhopitalError::DomainError`]
+    /// rather than guess what limit they stand for.
+    Constant(f64),
+    /// An exact fraction `numerator / denominator` in lowest terms, with a denominator
+    /// greater than one. Build it with [`Expression::rational`].
+    #[cfg(feature = "rational")]
+    Rational(i64, i64),
+    /// The circle constant π, kept symbolic so it is exact and shown by name.
+    Pi,
+    /// Euler's number e, kept symbolic so it is exact and shown by name.
+    E,
+    /// The variable `x`.
+    Variable,
+    /// Any other named variable, treated as a constant when differentiating with
+    /// respect to `x`.
+    Symbol(char),
+    Sum(Rc<Expression>, Rc<Expression>),
+    Difference(Rc<Expression>, Rc<Expression>),
+    Negate(Rc<Expression>),
+    Product(Rc<Expression>, Rc<Expression>),
+    Quotient(Rc<Expression>, Rc<Expression>),
+    /// The reciprocal `1 / f`, written `recip(f)`.
+    Reciprocal(Rc<Expression>),
+    Power(Rc<Expression>, f64),
+    /// A power whose exponent is itself an expression, such as `x^x`.
+    PowerExpr(Rc<Expression>, Rc<Expression>),
+    /// The `n`th root of the expression.
+    Root(Rc<Expression>, f64),
+    Sin(Rc<Expression>),
+    Cos(Rc<Expression>),
+    Tan(Rc<Expression>),
+    /// The secant, `1 / cos(f)`.
+    Sec(Rc<Expression>),
+    /// The cosecant, `1 / sin(f)`.
+    Csc(Rc<Expression>),
+    /// The cotangent, `cos(f) / sin(f)`.
+    Cot(Rc<Expression>),
+    Atan(Rc<Expression>),
+    Sinh(Rc<Expression>),
+    Cosh(Rc<Expression>),
+    /// The absolute value of the expression.
+    Abs(Rc<Expression>),
+    /// The sign of the expression, `1` or `-1`. It is undefined (NaN) at zero, where
+    /// it arises as the derivative of `|f|` at the kink.
+    Sign(Rc<Expression>),
+    Exp(Rc<Expression>),
+    Ln(Rc<Expression>),
+    /// Logarithm of the expression to a constant base.
+    Log(f64, Rc<Expression>),
+    /// The derivative of the expression with respect to `x`, left unevaluated until the
+    /// node is evaluated. Differentiating it gives the next derivative, still
+    /// unevaluated. [`Expression::differentiate_partial`] also leaves one wherever no
+    /// differentiation rule applies, and that evaluates to NaN.
+    Derivative(Rc<Expression>),
+    /// The first branch whose condition holds at `x`, or NaN where none does. Build it
+    /// with [`Expression::piecewise`].
+    Piecewise(Vec<(Condition, Rc<Expression>)>),
+}
+
+/// A comparison of `x` against a constant, selecting a branch of an
+/// [`Expression::Piecewise`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition {
+    /// `x < c`
+    Less(f64),
+    /// `x <= c`
+    LessOrEqual(f64),
+    /// `x > c`
+    Greater(f64),
+    /// `x >= c`
+    GreaterOrEqual(f64),
+}
+
+impl Condition {
+    /// The constant `x` is compared against.
+    pub fn bound(&self) -> f64 {
+        match *self {
+            Condition::Less(c)
+            | Condition::LessOrEqual(c)
+            | Condition::Greater(c)
+            | Condition::GreaterOrEqual(c) => c,
+        }
+    }
+
+    /// Whether the condition holds at `x`.
+    pub fn holds(&self, x: f64) -> bool {
+        match *self {
+            Condition::Less(c) => x < c,
+            Condition::LessOrEqual(c) => x <= c,
+            Condition::Greater(c) => x > c,
+            Condition::GreaterOrEqual(c) => x >= c,
+        }
+    }
+
+    // Whether the condition holds everywhere just beside `at` on `side`, which for an
+    // infinite `at` is the only side it can be approached from.
+    pub(crate) fn holds_beside(&self, at: f64, side: Direction) -> bool {
+        let c = self.bound();
+        match (self, side) {
+            (Condition::Less(_) | Condition::LessOrEqual(_), Direction::Left) => at <= c,
+            (Condition::Less(_) | Condition::LessOrEqual(_), _) => at < c,
+            (Condition::Greater(_) | Condition::GreaterOrEqual(_), Direction::Right) => at >= c,
+            (Condition::Greater(_) | Condition::GreaterOrEqual(_), _) => at > c,
+        }
+    }
+
+    // The same comparison against `bound + offset`.
+    fn offset(self, offset: f64) -> Condition {
+        match self {
+            Condition::Less(c) => Condition::Less(c + offset),
+            Condition::LessOrEqual(c) => Condition::LessOrEqual(c + offset),
+            Condition::Greater(c) => Condition::Greater(c + offset),
+            Condition::GreaterOrEqual(c) => Condition::GreaterOrEqual(c + offset),
+        }
+    }
+
+    // The condition on `x` that holds exactly when this one holds at `k * x`. With `k`
+    // zero that is either always or never, as comparing against an infinite bound is.
+    fn scaled(self, k: f64) -> Condition {
+        if k == 0.0 {
+            let bound = if self.holds(0.0) {
+                f64::INFINITY
+            } else {
+                f64::NEG_INFINITY
+            };
+            return Condition::Less(bound);
+        }
+        let flipped = k < 0.0;
+        match self {
+            Condition::Less(c) if flipped => Condition::Greater(c / k),
+            Condition::LessOrEqual(c) if flipped => Condition::GreaterOrEqual(c / k),
+            Condition::Greater(c) if flipped => Condition::Less(c / k),
+            Condition::GreaterOrEqual(c) if flipped => Condition::LessOrEqual(c / k),
+            Condition::Less(c) => Condition::Less(c / k),
+            Condition::LessOrEqual(c) => Condition::LessOrEqual(c / k),
+            Condition::Greater(c) => Condition::Greater(c / k),
+            Condition::GreaterOrEqual(c) => Condition::GreaterOrEqual(c / k),
+        }
+    }
+}
+
+// The branch selected at `x`.
+fn select(branches: &[(Condition, Rc<Expression>)], x: f64) -> Option<&Expression> {
+    branches
+        .iter()
+        .find(|(condition, _)| condition.holds(x))
+        .map(|(_, branch)| &**branch)
+}
+
+impl Expression {
+    // The direct subexpressions of this node, in order.
+    pub(crate) fn children(&self) -> impl Iterator<Item = &Expression> {
+        let branches = match self {
+            Expression::Piecewise(branches) => branches.as_slice(),
+            _ => &[],
+        };
+        let (first, second) = self.operands();
+        first
+            .into_iter()
+            .chain(second)
+            .chain(branches.iter().map(|(_, branch)| &**branch))
+    }
+
+    // The first and second direct subexpressions of any node but a piecewise one,
+    // whose branches are listed by `children` instead.
+    fn operands(&self) -> (Option<&Expression>, Option<&Expression>) {
+        match self {
+            Expression::Piecewise(_) => (None, None),
+            Expression::Constant(_)
+            | Expression::Pi
+            | Expression::E
+            | Expression::Variable
+            | Expression::Symbol(_) => (None, None),
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => (None, None),
+            Expression::Sum(a, b)
+            | Expression::Difference(a, b)
+            | Expression::Product(a, b)
+            | Expression::Quotient(a, b)
+            | Expression::PowerExpr(a, b) => (Some(a), Some(b)),
+            Expression::Negate(a)
+            | Expression::Power(a, _)
+            | Expression::Root(a, _)
+            | Expression::Sin(a)
+            | Expression::Cos(a)
+            | Expression::Tan(a)
+            | Expression::Reciprocal(a)
+            | Expression::Sec(a)
+            | Expression::Csc(a)
+            | Expression::Cot(a)
+            | Expression::Atan(a)
+            | Expression::Sinh(a)
+            | Expression::Cosh(a)
+            | Expression::Abs(a)
+            | Expression::Sign(a)
+            | Expression::Exp(a)
+            | Expression::Ln(a)
+            | Expression::Derivative(a)
+            | Expression::Log(_, a) => (Some(a), None),
+        }
+    }
+
+    // Rebuilds this node with each direct subexpression replaced by `f(child)`.
+    pub(crate) fn map_children(&self, mut f: impl FnMut(&Expression) -> Expression) -> Expression {
+        let mut map = |child: &Expression| Rc::new(f(child));
+        match self {
+            Expression::Constant(_)
+            | Expression::Pi
+            | Expression::E
+            | Expression::Variable
+            | Expression::Symbol(_) => self.clone(),
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => self.clone(),
+            Expression::Sum(a, b) => Expression::Sum(map(a), map(b)),
+            Expression::Difference(a, b) => Expression::Difference(map(a), map(b)),
+            Expression::Negate(a) => Expression::Negate(map(a)),
+            Expression::Product(a, b) => Expression::Product(map(a), map(b)),
+            Expression::Quotient(a, b) => Expression::Quotient(map(a), map(b)),
+            Expression::Power(a, n) => Expression::Power(map(a), *n),
+            Expression::PowerExpr(a, b) => Expression::PowerExpr(map(a), map(b)),
+            Expression::Root(a, n) => Expression::Root(map(a), *n),
+            Expression::Sin(a) => Expression::Sin(map(a)),
+            Expression::Cos(a) => Expression::Cos(map(a)),
+            Expression::Tan(a) => Expression::Tan(map(a)),
+            Expression::Reciprocal(a) => Expression::Reciprocal(map(a)),
+            Expression::Sec(a) => Expression::Sec(map(a)),
+            Expression::Csc(a) => Expression::Csc(map(a)),
+            Expression::Cot(a) => Expression::Cot(map(a)),
+            Expression::Atan(a) => Expression::Atan(map(a)),
+            Expression::Sinh(a) => Expression::Sinh(map(a)),
+            Expression::Cosh(a) => Expression::Cosh(map(a)),
+            Expression::Abs(a) => Expression::Abs(map(a)),
+            Expression::Sign(a) => Expression::Sign(map(a)),
+            Expression::Exp(a) => Expression::Exp(map(a)),
+            Expression::Ln(a) => Expression::Ln(map(a)),
+            Expression::Derivative(a) => Expression::Derivative(map(a)),
+            Expression::Log(base, a) => Expression::Log(*base, map(a)),
+            Expression::Piecewise(branches) => Expression::Piecewise(
+                branches
+                    .iter()
+                    .map(|(condition, branch)| (*condition, map(branch)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Replaces every occurrence of `x` with `replacement`, composing `f(x)` into
+    /// `f(replacement)`. The conditions of an [`Expression::Piecewise`] cannot hold an
+    /// expression, so they keep comparing `x` itself and only the branches change.
+    pub fn substitute(&self, replacement: &Expression) -> Expression {
+        match self {
+            Expression::Variable => replacement.clone(),
+            // The derivative has to be taken before composing, since f'(g) is not d/dx(f(g)).
+            Expression::Derivative(a) if let Ok(derivative) = a.differentiate() => {
+                derivative.substitute(replacement)
+            }
+            other => other.map_children(|child| child.substitute(replacement)),
+        }
+    }
+
+    /// Replaces `x` with `x + at`, so that a limit as `x -> at` becomes a limit of the
+    /// shifted expression as `x -> 0`.
+    pub fn shift(&self, at: f64) -> Expression {
+        match self {
+            Expression::Variable => Expression::Variable + at,
+            // x + at < c exactly when x < c - at
+            Expression::Piecewise(branches) => Expression::Piecewise(
+                branches
+                    .iter()
+                    .map(|(condition, branch)| (condition.offset(-at), Rc::new(branch.shift(at))))
+                    .collect(),
+            ),
+            other => other.map_children(|child| child.shift(at)),
+        }
+    }
+
+    /// Replaces `x` with `k * x`, for changing the variable of a limit: a limit of `f(x)`
+    /// as `x -> k * a` is the limit of the scaled expression as `x -> a`. Piecewise
+    /// conditions are rescaled to match, flipping for a negative `k`.
+    pub fn scale_variable(&self, k: f64) -> Expression {
+        match self {
+            Expression::Variable => Expression::Product(
+                Rc::new(Expression::Constant(k)),
+                Rc::new(Expression::Variable),
+            ),
+            Expression::Piecewise(branches) => Expression::Piecewise(
+                branches
+                    .iter()
+                    .map(|(condition, branch)| {
+                        (condition.scaled(k), Rc::new(branch.scale_variable(k)))
+                    })
+                    .collect(),
+            ),
+            // As for `substitute`, f'(k*x) is not d/dx(f(k*x)).
+            Expression::Derivative(a) if let Ok(derivative) = a.differentiate() => {
+                derivative.scale_variable(k)
+            }
+            other => other.map_children(|child| child.scale_variable(k)),
+        }
+    }
+
+    /// Applies `f` to every numeric constant, e.g. to rescale a problem into other
+    /// units: doubling the constants of `2*x + 3` gives `4*x + 6`.
+    ///
+    /// Exponents, root degrees, logarithm bases and piecewise bounds are left alone,
+    /// since they describe the shape of the expression rather than quantities in it.
+    /// `π` and `e` are left alone too.
+    pub fn map_constants<F: Fn(f64) -> f64>(&self, f: F) -> Expression {
+        self.map_constants_with(&f)
+    }
+
+    fn map_constants_with(&self, f: &dyn Fn(f64) -> f64) -> Expression {
+        match self {
+            Expression::Constant(c) => Expression::Constant(f(*c)),
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => Expression::Constant(f(*n as f64 / *d as f64)),
+            other => other.map_children(|child| child.map_constants_with(f)),
+        }
+    }
+
+    /// Caps every [`Expression::Power`] exponent at `max_abs` in magnitude, so `x^1000`
+    /// becomes `x^50` for a `max_abs` of 50, keeping exploratory evaluation finite.
+    ///
+    /// This changes what the expression means and is never applied by the solvers
+    /// themselves; use [`Expression::check_exponents`] to reject such exponents
+    /// instead.
+    pub fn clamp_exponents(&self, max_abs: f64) -> Expression {
+        match self {
+            Expression::Power(base, exp) => Expression::Power(
+                Rc::new(base.clamp_exponents(max_abs)),
+                exp.clamp(-max_abs, max_abs),
+            ),
+            other => other.map_children(|child| child.clamp_exponents(max_abs)),
+        }
+    }
+
+    /// Returns [`LhopitalError::ExponentTooLarge`] if any [`Expression::Power`]
+    /// exponent is larger than `max_abs` in magnitude.
+    pub fn check_exponents(&self, max_abs: f64) -> Result<(), LhopitalError> {
+        if self
+            .iter_nodes()
+            .any(|node| matches!(node, Expression::Power(_, exp) if exp.abs() > max_abs))
+        {
+            Err(LhopitalError::ExponentTooLarge { limit: max_abs })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Every node of the tree in preorder, starting with the expression itself.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = &Expression> {
+        let mut pending = vec![self];
+        std::iter::from_fn(move || {
+            let node = pending.pop()?;
+            let first_child = pending.len();
+            pending.extend(node.children());
+            pending[first_child..].reverse();
+            Some(node)
+        })
+    }
+
+    /// Whether `x` or any other variable appears anywhere in the expression. A
+    /// piecewise expression depends on `x` through its conditions.
+    pub fn contains_variable(&self) -> bool {
+        // Recursing avoids the allocation of `iter_nodes`, which matters because
+        // `simplify` asks this of every subtree through `is_zero` and `is_one`.
+        matches!(
+            self,
+            Expression::Variable | Expression::Symbol(_) | Expression::Piecewise(_)
+        ) || self.children().any(Expression::contains_variable)
+    }
+
+    /// Whether the expression is a plain number, independent of every variable.
+    pub fn is_constant(&self) -> bool {
+        !self.contains_variable()
+    }
+
+    /// The value of the expression if it does not depend on any variable.
+    pub fn constant_value(&self) -> Option<f64> {
+        self.is_constant().then(|| self.evaluate(0.0))
+    }
+
+    /// Whether the expression is constantly `0`, like `0` itself or `2 - 2`.
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Expression::Constant(c) => *c == 0.0,
+            _ => self.constant_value() == Some(0.0),
+        }
+    }
+
+    /// Whether the expression is constantly `1`, like `1` itself or `2 / 2`.
+    pub fn is_one(&self) -> bool {
+        match self {
+            Expression::Constant(c) => *c == 1.0,
+            _ => self.constant_value() == Some(1.0),
+        }
+    }
+
+    /// The number of nodes on the longest path from the root to a leaf, so a lone
+    /// constant or variable has depth 1.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut pending = vec![(self, 1)];
+        while let Some((expr, depth)) = pending.pop() {
+            deepest = deepest.max(depth);
+            pending.extend(expr.children().map(|child| (child, depth + 1)));
+        }
+        deepest
+    }
+
+    /// The total number of nodes in the tree.
+    pub fn node_count(&self) -> usize {
+        self.iter_nodes().count()
+    }
+
+    /// Returns [`LhopitalError::TooDeep`] if the expression is nested more than `limit`
+    /// levels deep. The check itself is iterative, so it is safe on any tree.
+    pub fn check_depth(&self, limit: usize) -> Result<(), LhopitalError> {
+        let mut pending = vec![(self, 1)];
+        while let Some((expr, depth)) = pending.pop() {
+            if depth > limit {
+                return Err(LhopitalError::TooDeep { limit });
+            }
+            pending.extend(expr.children().map(|child| (child, depth + 1)));
+        }
+        Ok(())
+    }
+
+    /// Evaluates the expression at `x`.
+    ///
+    /// Points outside the domain of the expression, such as `ln(-1)` or `(-1)^0.5`,
+    /// evaluate to NaN, as do any variables other than `x`; use
+    /// [`Expression::evaluate_with`] to assign those.
+    ///
+    /// Infinities follow IEEE arithmetic, whether they come from an overflow or from a
+    /// value like `ln(0)`: `∞ + 1` is `∞` and `1/∞` is `0`, while the indeterminate
+    /// `∞ - ∞`, `0 · ∞` and `∞ / ∞` are NaN. A NaN operand always makes its node NaN,
+    /// including `NaN^0` and `1^NaN`, which IEEE `pow` takes to be 1.
+    pub fn evaluate(&self, x: f64) -> f64 {
+        self.eval(x, None)
+    }
+
+    /// Evaluates the expression with each variable, including `'x'`, taking its value
+    /// from `vars`. Unassigned variables evaluate to NaN.
+    pub fn evaluate_with(&self, vars: &HashMap<char, f64>) -> f64 {
+        self.eval(vars.get(&'x').copied().unwrap_or(f64::NAN), Some(vars))
+    }
+
+    /// Evaluates the expression at `x`, failing instead of returning a NaN or infinite
+    /// value.
+    ///
+    /// Unlike [`Expression::evaluate`], a bad subexpression is reported even when the
+    /// result would mask it, as in `exp(ln(0))`. The error names the innermost
+    /// subexpression without a finite value.
+    pub fn evaluate_checked(&self, x: f64) -> Result<f64, LhopitalError> {
+        // Only the selected branch is evaluated, so the others may be undefined at `x`.
+        if let Expression::Piecewise(branches) = self {
+            return select(branches, x)
+                .ok_or_else(|| LhopitalError::DomainError(self.to_string()))?
+                .evaluate_checked(x);
+        }
+        // The value of a derivative depends on its argument around `x`, not just at it.
+        if let Expression::Derivative(a) = self {
+            return a.differentiate()?.evaluate_checked(x);
+        }
+        let mut error = None;
+        // Reduce each child to its checked value so only this node is evaluated here.
+        let reduced = self.map_children(|child| match child.evaluate_checked(x) {
+            Ok(value) => Expression::Constant(value),
+            Err(e) => {
+                error.get_or_insert(e);
+                Expression::Constant(f64::NAN)
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+        let value = reduced.evaluate(x);
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(LhopitalError::DomainError(self.to_string()))
+        }
+    }
+
+    /// Checks that `x` is in the domain of every subexpression, failing with
+    /// [`LhopitalError::DomainError`] naming the innermost one it is not in.
+    ///
+    /// Logarithms of non-positive numbers, even or fractional roots and powers of
+    /// negative numbers and divisions by zero are outside the domain, as is anything
+    /// else that is NaN for defined operands, like `tan(pi/2)` or a variable other
+    /// than `x`. Unlike [`Expression::evaluate_checked`], values that merely overflow,
+    /// like `exp(1000)`, pass.
+    pub fn domain_check(&self, x: f64) -> Result<(), LhopitalError> {
+        let outside = || LhopitalError::DomainError(self.to_string());
+        // Only the selected branch is evaluated, so the others may be undefined at `x`.
+        if let Expression::Piecewise(branches) = self {
+            return select(branches, x).ok_or_else(outside)?.domain_check(x);
+        }
+        for child in self.children() {
+            child.domain_check(x)?;
+        }
+        let pole = match self {
+            Expression::Quotient(_, a) | Expression::Reciprocal(a) => a.evaluate(x) == 0.0,
+            Expression::Ln(a) | Expression::Log(_, a) => a.evaluate(x) <= 0.0,
+            Expression::Power(base, exp) => *exp < 0.0 && base.evaluate(x) == 0.0,
+            Expression::PowerExpr(base, exp) => exp.evaluate(x) < 0.0 && base.evaluate(x) == 0.0,
+            _ => false,
+        };
+        if pole || self.evaluate(x).is_nan() {
+            Err(outside())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Evaluates the expression at each of `xs`.
+    pub fn evaluate_many(&self, xs: &[f64]) -> Vec<f64> {
+        xs.iter().map(|&x| self.evaluate(x)).collect()
+    }
+
+    /// Evaluates the expression at `x` like [`Expression::evaluate`], giving the same
+    /// value, but walks the tree with an explicit stack instead of recursing, so it is
+    /// safe on trees of any depth.
+    ///
+    /// The recursive [`Expression::evaluate`] is faster on bushy trees, while this is
+    /// faster on deep, narrow ones like a long chain of sums. The argument of an
+    /// [`Expression::Derivative`] is still differentiated recursively.
+    pub fn evaluate_iter(&self, x: f64) -> f64 {
+        enum Task<'a> {
+            Visit(&'a Expression),
+            // Combines the values of the node's operands, which are on top of the value
+            // stack, the last operand topmost.
+            Combine(&'a Expression, usize),
+        }
+
+        let mut tasks = vec![Task::Visit(self)];
+        let mut values = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(Expression::Piecewise(branches)) => match select(branches, x) {
+                    Some(branch) => tasks.push(Task::Visit(branch)),
+                    None => values.push(f64::NAN),
+                },
+                Task::Visit(Expression::Derivative(a)) => values.push(
+                    a.differentiate()
+                        .map_or(f64::NAN, |derivative| derivative.evaluate_iter(x)),
+                ),
+                Task::Visit(expr) => match expr.operands() {
+                    (None, _) => values.push(expr.eval_node(x, None, f64::NAN, f64::NAN)),
+                    (Some(a), None) => {
+                        tasks.push(Task::Combine(expr, 1));
+                        tasks.push(Task::Visit(a));
+                    }
+                    (Some(a), Some(b)) => {
+                        tasks.push(Task::Combine(expr, 2));
+                        tasks.push(Task::Visit(b));
+                        tasks.push(Task::Visit(a));
+                    }
+                },
+                Task::Combine(expr, arity) => {
+                    let b = if arity == 2 { values.pop() } else { None };
+                    let a = values.pop();
+                    let value =
+                        expr.eval_node(x, None, a.unwrap_or(f64::NAN), b.unwrap_or(f64::NAN));
+                    values.push(value);
+                }
+            }
+        }
+        values.pop().unwrap_or(f64::NAN)
+    }
+
+    /// Borrows the expression as a closure calling [`Expression::evaluate`], for APIs
+    /// such as plotting libraries that take an `Fn(f64) -> f64`.
+    pub fn to_function(&self) -> impl Fn(f64) -> f64 + '_ {
+        move |x| self.evaluate(x)
+    }
+
+    fn eval(&self, x: f64, vars: Option<&HashMap<char, f64>>) -> f64 {
+        match self {
+            Expression::Piecewise(branches) => {
+                select(branches, x).map_or(f64::NAN, |branch| branch.eval(x, vars))
+            }
+            // A derivative without a differentiation rule has no known value.
+            Expression::Derivative(a) => a
+                .differentiate()
+                .map_or(f64::NAN, |derivative| derivative.eval(x, vars)),
+            _ => {
+                let value = |operand: Option<&Expression>| {
+                    operand.map_or(f64::NAN, |operand| operand.eval(x, vars))
+                };
+                let (a, b) = self.operands();
+                self.eval_node(x, vars, value(a), value(b))
+            }
+        }
+    }
+
+    // The value of this node given the values `a` and `b` of its operands, in order,
+    // with NaN for operands it does not have. Piecewise expressions and derivatives do
+    // not evaluate all of their operands, so the caller evaluates them instead. Inlined
+    // so that the recursion in `eval` costs no more than a single match.
+    #[inline(always)]
+    fn eval_node(&self, x: f64, vars: Option<&HashMap<char, f64>>, a: f64, b: f64) -> f64 {
+        match self {
+            Expression::Constant(c) => *c,
+            Expression::Pi => std::f64::consts::PI,
+            Expression::E => std::f64::consts::E,
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => *n as f64 / *d as f64,
+            Expression::Variable => x,
+            Expression::Symbol(name) => vars
+                .and_then(|vars| vars.get(name))
+                .copied()
+                .unwrap_or(f64::NAN),
+            Expression::Sum(..) => a + b,
+            Expression::Difference(..) => a - b,
+            Expression::Negate(_) => -a,
+            Expression::Product(..) => a * b,
+            // An exact zero denominator has no meaningful value, so report NaN rather
+            // than the signed infinity IEEE division would produce.
+            Expression::Quotient(..) => {
+                if b == 0.0 {
+                    f64::NAN
+                } else {
+                    a / b
+                }
+            }
+            // Unlike a quotient, a zero gives the signed infinity `x^-1` would, since the
+            // `0·∞` rewrite relies on the reciprocal of a vanishing factor being unbounded.
+            Expression::Reciprocal(_) => 1.0 / a,
+            // A negative base with a fractional exponent has no real value and is NaN;
+            // results too large for an f64 overflow to ±inf. Integer exponents use
+            // repeated multiplication, which is also faster, and it is exact for whole
+            // numbers whose power fits in the mantissa, so `3^2` is exactly 9.
+            Expression::Power(_, exp) => {
+                let base = a;
+                if base.is_nan() {
+                    f64::NAN
+                } else if exp.fract() == 0.0 && exp.abs() <= i32::MAX as f64 {
+                    base.powi(*exp as i32)
+                } else {
+                    base.powf(*exp)
+                }
+            }
+            Expression::PowerExpr(..) => {
+                let (base, exp) = (a, b);
+                if base.is_nan() || exp.is_nan() {
+                    f64::NAN
+                } else {
+                    base.powf(exp)
+                }
+            }
+            // Odd integer roots of negative numbers are real; even (or fractional)
+            // roots of negative numbers are NaN.
+            Expression::Root(_, n) => {
+                let arg = a;
+                if *n == 2.0 {
+                    arg.sqrt()
+                } else if arg < 0.0 && n.fract() == 0.0 && n % 2.0 != 0.0 {
+                    -(-arg).powf(1.0 / n)
+                } else {
+                    arg.powf(1.0 / n)
+                }
+            }
+            Expression::Sin(_) => a.sin(),
+            Expression::Cos(_) => a.cos(),
+            // The nearest floats to the asymptotes at π/2 + kπ give huge but finite
+            // values, so those points are reported as undefined instead.
+            Expression::Tan(_) => {
+                let arg = a;
+                if arg.cos().abs() < f64::EPSILON {
+                    f64::NAN
+                } else {
+                    arg.tan()
+                }
+            }
+            Expression::Abs(_) => a.abs(),
+            Expression::Sign(_) => {
+                let arg = a;
+                if arg == 0.0 { f64::NAN } else { arg.signum() }
+            }
+            Expression::Sinh(_) => a.sinh(),
+            Expression::Cosh(_) => a.cosh(),
+            Expression::Atan(_) => a.atan(),
+            // Like `tan`, the reciprocal functions are undefined where the function
+            // they are the reciprocal of vanishes up to rounding.
+            Expression::Sec(_) => {
+                let cos = a.cos();
+                if cos.abs() < f64::EPSILON {
+                    f64::NAN
+                } else {
+                    1.0 / cos
+                }
+            }
+            Expression::Csc(_) => {
+                let sin = a.sin();
+                if sin.abs() < f64::EPSILON {
+                    f64::NAN
+                } else {
+                    1.0 / sin
+                }
+            }
+            Expression::Cot(_) => {
+                let arg = a;
+                if arg.sin().abs() < f64::EPSILON {
+                    f64::NAN
+                } else {
+                    arg.cos() / arg.sin()
+                }
+            }
+            // Overflows to +inf once the exponent passes ~709.78 (f64::MAX.ln()). That is
+            // left as-is so callers can tell an unbounded value from a large finite one.
+            Expression::Exp(_) => a.exp(),
+            // ln(0) is -inf, matching the limit from the right; negative arguments are NaN.
+            Expression::Ln(_) => {
+                let arg = a;
+                if arg < 0.0 { f64::NAN } else { arg.ln() }
+            }
+            // Undefined (NaN) for bases that are non-positive or 1 as well as for negative
+            // arguments.
+            Expression::Log(base, _) => {
+                let arg = a;
+                if *base <= 0.0 || *base == 1.0 || arg < 0.0 {
+                    f64::NAN
+                } else {
+                    arg.ln() / base.ln()
+                }
+            }
+            Expression::Piecewise(_) | Expression::Derivative(_) => {
+                unreachable!("piecewise expressions and derivatives are evaluated by the caller")
+            }
+        }
+    }
+
+    /// Estimates the value as `x` tends to `sign * ∞`, from samples at `sign * scale`
+    /// and ten times further out.
+    ///
+    /// Growing by at least a tenth between the two samples counts as tending to an
+    /// infinity, and shrinking as much as tending to zero, so `1/x` gives `0` and
+    /// `ln(x)` gives `+inf`. Otherwise the value at `sign * scale` is returned. The
+    /// trend assumes the expression is eventually monotonic, so an oscillating one
+    /// like `sin(x)` gives no meaningful result.
+    pub fn evaluate_at_infinity(&self, sign: f64, scale: f64) -> f64 {
+        trend_at_infinity(|x| self.evaluate(x), sign, scale)
+    }
+
+    /// `(f(x + h) - f(x - h)) / 2h`, for checking symbolic derivatives.
+    pub fn numeric_derivative(&self, x: f64, h: f64) -> f64 {
+        (self.evaluate(x + h) - self.evaluate(x - h)) / (2.0 * h)
+    }
+
+    /// The slope at `x`, from evaluating the symbolic derivative rather than a finite
+    /// difference, so it is exact up to floating point. NaN if a subexpression has no
+    /// differentiation rule.
+    pub fn derivative_at(&self, x: f64) -> f64 {
+        self.differentiate()
+            .map_or(f64::NAN, |derivative| derivative.evaluate(x))
+    }
+
+    /// The roots of the expression within `window` of `center`, in increasing order.
+    ///
+    /// The interval is split into `samples` equal parts, and every part whose ends have
+    /// opposite signs is narrowed down by bisection. Only roots where the sign changes
+    /// are found, so a double root like that of `(x - 2)^2` is missed unless it falls on
+    /// a sample point, as are roots closer together than the parts are wide. A sign
+    /// change across a pole, as for `1/x`, is not a root and is skipped.
+    pub fn roots_near(&self, center: f64, window: f64, samples: usize) -> Vec<f64> {
+        let samples = samples.max(1);
+        let start = center - window.abs();
+        let step = 2.0 * window.abs() / samples as f64;
+        let mut roots = Vec::new();
+        let mut previous = (start, self.evaluate(start));
+        if previous.1 == 0.0 {
+            roots.push(start);
+        }
+        for i in 1..=samples {
+            let x = start + step * i as f64;
+            let value = self.evaluate(x);
+            if value == 0.0 {
+                roots.push(x);
+            } else if previous.1 * value < 0.0
+                && let Some(root) = self.bisect(previous, (x, value))
+            {
+                roots.push(root);
+            }
+            previous = (x, value);
+        }
+        roots
+    }
+
+    // Narrows down a sign change between `a` and `b` until the two meet, returning the
+    // end closer to zero. A value that grows instead of shrinking means a pole.
+    fn bisect(&self, (mut a, mut fa): (f64, f64), (mut b, mut fb): (f64, f64)) -> Option<f64> {
+        let bound = fa.abs().min(fb.abs());
+        loop {
+            let mid = a + (b - a) / 2.0;
+            if mid <= a || mid >= b {
+                break;
+            }
+            let value = self.evaluate(mid);
+            if value == 0.0 {
+                return Some(mid);
+            } else if value.is_nan() {
+                return None;
+            } else if value.signum() == fa.signum() {
+                (a, fa) = (mid, value);
+            } else {
+                (b, fb) = (mid, value);
+            }
+        }
+        let (root, value) = if fa.abs() <= fb.abs() {
+            (a, fa)
+        } else {
+            (b, fb)
+        };
+        (value.abs() < bound).then_some(root)
+    }
+
+    /// Compares two expressions numerically by evaluating both at every sample point.
+    ///
+    /// Values match if they are within `tol` of each other, are the same infinity, or
+    /// are both NaN.
+    pub fn approx_equal(&self, other: &Expression, samples: &[f64], tol: f64) -> bool {
+        samples.iter().all(|&x| {
+            let (a, b) = (self.evaluate(x), other.evaluate(x));
+            (a.is_nan() && b.is_nan()) || a == b || (a - b).abs() <= tol
+        })
+    }
+
+    /// Compares two trees structurally like `==`, except that numbers in them, such as
+    /// constants, exponents and piecewise bounds, match if they are within `eps` of each
+    /// other. `3 * x` and `2.9999999999 * x` match for a small `eps`, but `x + 1` and
+    /// `1 + x` still do not.
+    pub fn structurally_eq_within(&self, other: &Expression, eps: f64) -> bool {
+        let close = |a: f64, b: f64| a == b || (a - b).abs() <= eps;
+        let same_node = match (self, other) {
+            (Expression::Constant(a), Expression::Constant(b))
+            | (Expression::Power(_, a), Expression::Power(_, b))
+            | (Expression::Root(_, a), Expression::Root(_, b))
+            | (Expression::Log(a, _), Expression::Log(b, _)) => close(*a, *b),
+            (Expression::Symbol(a), Expression::Symbol(b)) => a == b,
+            #[cfg(feature = "rational")]
+            (Expression::Rational(..), Expression::Rational(..)) => self == other,
+            (Expression::Piecewise(a), Expression::Piecewise(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|((a, _), (b, _))| {
+                        std::mem::discriminant(a) == std::mem::discriminant(b)
+                            && close(a.bound(), b.bound())
+                    })
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        };
+        same_node
+            && self
+                .children()
+                .zip(other.children())
+                .all(|(a, b)| a.structurally_eq_within(b, eps))
+    }
+
+    /// Returns the derivative of the expression with respect to `x`, or an error if a
+    /// subexpression has no differentiation rule.
+    pub fn differentiate(&self) -> Result<Self, LhopitalError> {
+        self.differentiate_wrt('x')
+    }
+
+    /// Returns the partial derivative with respect to `var`, treating every other
+    /// variable as a constant. `'x'` refers to [`Expression::Variable`].
+    pub fn differentiate_wrt(&self, var: char) -> Result<Self, LhopitalError> {
+        self.derive(var, false, None)
+    }
+
+    /// Like [`Expression::differentiate`], but a subexpression with no differentiation
+    /// rule is left as an [`Expression::Derivative`] placeholder instead of failing the
+    /// whole tree, so `x^2 + sign(x)` gives `2 * x + d/dx(sign(x))`.
+    pub fn differentiate_partial(&self) -> Self {
+        self.derive('x', true, None)
+            .expect("partial differentiation has a rule for every node")
+    }
+
+    /// Returns the `n`th derivative with respect to `x`, simplifying between steps so
+    /// the tree stays small. The 0th derivative is the expression itself.
+    pub fn differentiate_n(&self, n: usize) -> Result<Self, LhopitalError> {
+        let mut derivative = self.clone();
+        for _ in 0..n {
+            derivative = derivative.differentiate()?.simplify();
+        }
+        Ok(derivative)
+    }
+
+    /// Like [`Expression::differentiate`], but looks up and records the derivative of
+    /// every subexpression in `cache`.
+    pub fn differentiate_cached(&self, cache: &mut DerivativeCache) -> Result<Self, LhopitalError> {
+        self.derive('x', false, Some(cache))
+    }
+
+    fn derive(
+        &self,
+        var: char,
+        partial: bool,
+        mut cache: Option<&mut DerivativeCache>,
+    ) -> Result<Self, LhopitalError> {
+        // Leaves are cheaper to differentiate than to look up.
+        let cacheable = self.children().next().is_some();
+        if cacheable
+            && let Some(derivative) = cache.as_deref().and_then(|cache| cache.get(var, self))
+        {
+            return Ok(derivative.clone());
+        }
+
+        let derivative = match self {
+            // d/dx(c) = 0
+            Expression::Constant(_) | Expression::Pi | Expression::E => Expression::zero(),
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => Expression::zero(),
+            // d/dx(x) = 1, d/dx(y) = 0
+            Expression::Variable => Expression::Constant(if var == 'x' { 1.0 } else { 0.0 }),
+            Expression::Symbol(name) => Expression::Constant(if *name == var { 1.0 } else { 0.0 }),
+            // d/dx(f+g) = f' + g'
+            Expression::Sum(a, b) => sum(
+                a.derive(var, partial, cache.as_deref_mut())?,
+                b.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(f-g) = f' - g'
+            Expression::Difference(a, b) => difference(
+                a.derive(var, partial, cache.as_deref_mut())?,
+                b.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(-f) = -f'
+            Expression::Negate(a) => negate(a.derive(var, partial, cache.as_deref_mut())?),
+            // d/dx(f1*f2*...*fn) = sum over i of f1*...*fi'*...*fn, taken over the
+            // flattened factors so each one is differentiated exactly once
+            Expression::Product(..) => {
+                let mut factors = Vec::new();
+                flatten_factors(self, &mut factors);
+                let mut derivative = Expression::zero();
+                for i in 0..factors.len() {
+                    let term = factors.iter().enumerate().try_fold(
+                        Expression::Constant(1.0),
+                        |term, (j, factor)| {
+                            Ok::<_, LhopitalError>(if i == j {
+                                product(term, factor.derive(var, partial, cache.as_deref_mut())?)
+                            } else {
+                                product(term, (*factor).clone())
+                            })
+                        },
+                    )?;
+                    derivative = sum(derivative, term);
+                }
+                derivative
+            }
+            // d/dx(f/g) = (f'g - fg') / g^2
+            Expression::Quotient(a, b) => quotient(
+                difference(
+                    product(a.derive(var, partial, cache.as_deref_mut())?, (**b).clone()),
+                    product((**a).clone(), b.derive(var, partial, cache.as_deref_mut())?),
+                ),
+                Expression::Power(b.clone(), 2.0),
+            ),
+            // d/dx(1/f) = -f' / f^2
+            Expression::Reciprocal(a) => quotient(
+                negate(a.derive(var, partial, cache.as_deref_mut())?),
+                Expression::Power(a.clone(), 2.0),
+            ),
+            // d/dx(f^0) = 0, since f^0 = 1 however f behaves, so f' is never needed
+            Expression::Power(_, exp) if *exp == 0.0 => Expression::zero(),
+            // d/dx(f^1) = f', which also holds where f = 0 and f^0 is undefined
+            Expression::Power(base, exp) if *exp == 1.0 => {
+                base.derive(var, partial, cache.as_deref_mut())?
+            }
+            // d/dx(x^n) = n*x^(n-1) (Power Rule simplified for this example)
+            // d/dx(f^n) = n*f^(n-1) * f' (Chain Rule for any other base)
+            Expression::Power(base, exp) => {
+                let reduced = product(
+                    Expression::Constant(*exp),
+                    power((**base).clone(), exp - 1.0),
+                );
+                if let Expression::Variable = **base
+                    && var == 'x'
+                {
+                    reduced
+                } else {
+                    product(reduced, base.derive(var, partial, cache.as_deref_mut())?)
+                }
+            }
+            // d/dx(f^g) = f^g * (g' * ln(f) + g * f'/f)
+            Expression::PowerExpr(base, exp) => product(
+                self.clone(),
+                sum(
+                    product(
+                        exp.derive(var, partial, cache.as_deref_mut())?,
+                        Expression::Ln(base.clone()),
+                    ),
+                    product(
+                        (**exp).clone(),
+                        quotient(
+                            base.derive(var, partial, cache.as_deref_mut())?,
+                            (**base).clone(),
+                        ),
+                    ),
+                ),
+            ),
+            // d/dx(root_n(f)) = (1/n) * f^(1/n - 1) * f', with f^(1/n - 1) written as
+            // root_n(f)^(1 - n) so odd roots stay defined for negative f
+            Expression::Root(a, n) => product(
+                Expression::Product(
+                    Rc::new(reciprocal(*n)),
+                    Rc::new(Expression::Power(
+                        Rc::new(Expression::Root(a.clone(), *n)),
+                        1.0 - n,
+                    )),
+                ),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(sin(f)) = cos(f) * f'
+            Expression::Sin(a) => product(
+                Expression::Cos(a.clone()),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(cos(f)) = -sin(f) * f'
+            Expression::Cos(a) => product(
+                Expression::Negate(Rc::new(Expression::Sin(a.clone()))),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(tan(f)) = f' / cos(f)^2
+            Expression::Tan(a) => quotient(
+                a.derive(var, partial, cache.as_deref_mut())?,
+                Expression::Power(Rc::new(Expression::Cos(a.clone())), 2.0),
+            ),
+            // d/dx(|f|) = sign(f) * f', undefined where f = 0
+            Expression::Abs(a) => product(
+                Expression::Sign(a.clone()),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // sign(f) is constant wherever it is defined, but its derivative at zero has
+            // no real value, so there is no rule for it
+            Expression::Sign(_) if partial => Expression::Derivative(Rc::new(self.clone())),
+            Expression::Sign(_) => {
+                return Err(LhopitalError::DifferentiationUnsupported(self.to_string()));
+            }
+            // d/dy(d/dx(f)) = d/dx(d/dy(f)), so with `y = x` this is the next derivative
+            Expression::Derivative(a) => {
+                Expression::Derivative(Rc::new(a.derive(var, partial, cache.as_deref_mut())?))
+            }
+            // d/dx(sinh(f)) = cosh(f) * f'
+            Expression::Sinh(a) => product(
+                Expression::Cosh(a.clone()),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(cosh(f)) = sinh(f) * f'
+            Expression::Cosh(a) => product(
+                Expression::Sinh(a.clone()),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(atan(f)) = f' / (1 + f^2)
+            Expression::Atan(a) => quotient(
+                a.derive(var, partial, cache.as_deref_mut())?,
+                Expression::Constant(1.0) + Expression::Power(a.clone(), 2.0),
+            ),
+            // d/dx(sec(f)) = sec(f) * tan(f) * f'
+            Expression::Sec(a) => product(
+                product(self.clone(), Expression::Tan(a.clone())),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(csc(f)) = -csc(f) * cot(f) * f'
+            Expression::Csc(a) => product(
+                negate(product(self.clone(), Expression::Cot(a.clone()))),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(cot(f)) = -f' / sin(f)^2
+            Expression::Cot(a) => negate(quotient(
+                a.derive(var, partial, cache.as_deref_mut())?,
+                Expression::Power(Rc::new(Expression::Sin(a.clone())), 2.0),
+            )),
+            // d/dx(e^f) = e^f * f'
+            Expression::Exp(a) => product(
+                Expression::Exp(a.clone()),
+                a.derive(var, partial, cache.as_deref_mut())?,
+            ),
+            // d/dx(ln(f)) = f' / f
+            Expression::Ln(a) => {
+                quotient(a.derive(var, partial, cache.as_deref_mut())?, (**a).clone())
+            }
+            // d/dx(log_b(f)) = f' / (f * ln(b))
+            Expression::Log(base, a) => quotient(
+                a.derive(var, partial, cache.as_deref_mut())?,
+                Expression::Product(a.clone(), Rc::new(Expression::Constant(base.ln()))),
+            ),
+            // Branch by branch, so at a boundary this is the derivative of the branch
+            // selected there, which is one-sided.
+            Expression::Piecewise(branches) => Expression::Piecewise(
+                branches
+                    .iter()
+                    .map(|(condition, branch)| {
+                        Ok((
+                            *condition,
+                            Rc::new(branch.derive(var, partial, cache.as_deref_mut())?),
+                        ))
+                    })
+                    .collect::<Result<_, LhopitalError>>()?,
+            ),
+        };
+        if cacheable && let Some(cache) = cache {
+            cache.insert(var, self.clone(), derivative.clone());
+        }
+        Ok(derivative)
+    }
+
+    /// Like [`Expression::differentiate`], but also names the rule applied at each node,
+    /// such as `"power rule"` or `"product rule"`, for showing how the derivative was
+    /// found. Rules are listed from the root down, and `"chain rule"` follows the rule
+    /// of any function or power applied to something other than `x` itself, so
+    /// `sin(x^2)` gives `["trigonometric rule", "chain rule", "power rule"]`. Use
+    /// [`Expression::differentiate_annotated`] for the rules as a [`Rule`](crate::Rule)
+    /// tree instead.
+    pub fn differentiate_explained(&self) -> Result<(Self, Vec<String>), LhopitalError> {
+        let (derivative, tree) = self.differentiate_annotated()?;
+        let mut rules = Vec::new();
+        tree.flatten(&mut rules);
+        Ok((derivative, rules))
+    }
+
+    /// Returns the first `order + 1` Taylor coefficients around `at`, so that
+    /// `coefficients[k] = f⁽ᵏ⁾(at) / k!`.
+    pub fn taylor(&self, at: f64, order: usize) -> Result<Vec<f64>, LhopitalError> {
+        let mut coefficients = Vec::with_capacity(order + 1);
+        let mut derivative = self.simplify();
+        let mut factorial = 1.0;
+        for k in 0..=order {
+            if k > 0 {
+                derivative = derivative.differentiate()?.simplify();
+                factorial *= k as f64;
+            }
+            coefficients.push(derivative.evaluate(at) / factorial);
+        }
+        Ok(coefficients)
+    }
+}
+
+// Floats compare with `==`, so `Eq` only fails to be reflexive for trees holding a NaN
+// constant. Simplification never folds into a non-finite constant, so those trees
+// only arise from NaN or infinite constants in the input.
+impl Eq for Expression {}
+
+// Hashes the tree structurally, consistently with `PartialEq`: floats hash by their
+// bits, with `-0.0` hashed as `0.0` since the two compare equal.
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let hash_f64 = |value: f64, state: &mut H| {
+            let value = if value == 0.0 { 0.0 } else { value };
+            value.to_bits().hash(state);
+        };
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Expression::Constant(value)
+            | Expression::Power(_, value)
+            | Expression::Root(_, value)
+            | Expression::Log(value, _) => hash_f64(*value, state),
+            Expression::Symbol(name) => name.hash(state),
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => (n, d).hash(state),
+            Expression::Piecewise(branches) => {
+                branches.len().hash(state);
+                for (condition, _) in branches {
+                    std::mem::discriminant(condition).hash(state);
+                    hash_f64(condition.bound(), state);
+                }
+            }
+            Expression::Pi
+            | Expression::E
+            | Expression::Variable
+            | Expression::Sum(..)
+            | Expression::Difference(..)
+            | Expression::Negate(_)
+            | Expression::Product(..)
+            | Expression::Quotient(..)
+            | Expression::PowerExpr(..)
+            | Expression::Sin(_)
+            | Expression::Cos(_)
+            | Expression::Tan(_)
+            | Expression::Reciprocal(_)
+            | Expression::Sec(_)
+            | Expression::Csc(_)
+            | Expression::Cot(_)
+            | Expression::Atan(_)
+            | Expression::Sinh(_)
+            | Expression::Cosh(_)
+            | Expression::Abs(_)
+            | Expression::Sign(_)
+            | Expression::Exp(_)
+            | Expression::Ln(_)
+            | Expression::Derivative(_) => {}
+        }
+        for child in self.children() {
+            child.hash(state);
+        }
+    }
+}
+
+// Constructors used by `differentiate` that fold constant operands and the
+// identities the rules produce most often (`0 + f`, `1 * f`, `0 * f`, ...), so
+// derivatives come out compact without a separate `simplify` pass.
+
+// The trend of `f` as `x` tends to `sign * ∞`, as described on
+// `Expression::evaluate_at_infinity`.
+pub(crate) fn trend_at_infinity(f: impl Fn(f64) -> f64, sign: f64, scale: f64) -> f64 {
+    // Ratio between the magnitudes of the two samples that counts as a trend.
+    const TREND: f64 = 1.1;
+    let near = f(sign * scale);
+    let far = f(sign * scale * 10.0);
+    if !near.is_finite() || far.is_nan() {
+        near
+    } else if near != 0.0 && far.abs() >= TREND * near.abs() {
+        f64::INFINITY.copysign(far)
+    } else if far.abs() * TREND <= near.abs() {
+        0.0
+    } else {
+        near
+    }
+}
+
+fn sum(a: Expression, b: Expression) -> Expression {
+    #[cfg(feature = "rational")]
+    if let Some(exact) = rational::combine(&a, &b, Op::Add) {
+        return exact;
+    }
+    match (a, b) {
+        (Expression::Constant(a), Expression::Constant(b)) => Expression::Constant(a + b),
+        (Expression::Constant(0.0), other) | (other, Expression::Constant(0.0)) => other,
+        (a, b) => Expression::Sum(Rc::new(a), Rc::new(b)),
+    }
+}
+
+fn difference(a: Expression, b: Expression) -> Expression {
+    #[cfg(feature = "rational")]
+    if let Some(exact) = rational::combine(&a, &b, Op::Sub) {
+        return exact;
+    }
+    match (a, b) {
+        (Expression::Constant(a), Expression::Constant(b)) => Expression::Constant(a - b),
+        (a, Expression::Constant(0.0)) => a,
+        (Expression::Constant(0.0), b) => negate(b),
+        (a, b) => Expression::Difference(Rc::new(a), Rc::new(b)),
+    }
+}
+
+fn negate(a: Expression) -> Expression {
+    match a {
+        Expression::Constant(c) => Expression::Constant(-c),
+        #[cfg(feature = "rational")]
+        Expression::Rational(n, d) => rational::negate(n, d),
+        a => Expression::Negate(Rc::new(a)),
+    }
+}
+
+// Appends the factors of nested products to `factors`, left to right.
+fn flatten_factors<'a>(expr: &'a Expression, factors: &mut Vec<&'a Expression>) {
+    match expr {
+        Expression::Product(a, b) => {
+            flatten_factors(a, factors);
+            flatten_factors(b, factors);
+        }
+        factor => factors.push(factor),
+    }
+}
+
+fn product(a: Expression, b: Expression) -> Expression {
+    #[cfg(feature = "rational")]
+    if let Some(exact) = rational::combine(&a, &b, Op::Mul) {
+        return exact;
+    }
+    match (a, b) {
+        (Expression::Constant(a), Expression::Constant(b)) => Expression::Constant(a * b),
+        (Expression::Constant(0.0), _) | (_, Expression::Constant(0.0)) => Expression::zero(),
+        (Expression::Constant(1.0), other) | (other, Expression::Constant(1.0)) => other,
+        // c1 * (c2 * f) = (c1*c2) * f, as the power rule gives twice over for `x^3`.
+        (Expression::Constant(a), Expression::Product(ref c, ref rest))
+            if let Expression::Constant(b) = **c =>
+        {
+            product(Expression::Constant(a * b), (**rest).clone())
+        }
+        (a, b) => Expression::Product(Rc::new(a), Rc::new(b)),
+    }
+}
+
+// `1/n`, kept exact for whole `n` when rationals are enabled.
+fn reciprocal(n: f64) -> Expression {
+    #[cfg(feature = "rational")]
+    if let Some(exact) = rational::reciprocal(n) {
+        return exact;
+    }
+    Expression::Constant(1.0 / n)
+}
+
+fn power(base: Expression, exp: f64) -> Expression {
+    match exp {
+        0.0 => Expression::one(),
+        1.0 => base,
+        exp => Expression::Power(Rc::new(base), exp),
+    }
+}
+
+fn quotient(a: Expression, b: Expression) -> Expression {
+    #[cfg(feature = "rational")]
+    if let Some(exact) = rational::combine(&a, &b, Op::Div) {
+        return exact;
+    }
+    match (a, b) {
+        (Expression::Constant(a), Expression::Constant(b)) if b != 0.0 => {
+            Expression::Constant(a / b)
+        }
+        (a, Expression::Constant(1.0)) => a,
+        (a, b) => Expression::Quotient(Rc::new(a), Rc::new(b)),
+    }
+}
+
+// Dismantles the tree iteratively, since the default recursive drop would overflow the
+// stack on a deeply nested expression.
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        detach_children(self, &mut pending);
+        while let Some(mut expr) = pending.pop() {
+            detach_children(&mut expr, &mut pending);
+        }
+    }
+}
+
+// Moves the children of `expr` that have children of their own into `pending`, leaving
+// leaves behind in their place. Children shared with another tree are left alone, as
+// dropping them here only releases a reference.
+fn detach_children(expr: &mut Expression, pending: &mut Vec<Expression>) {
+    let mut detach = |child: &mut Rc<Expression>| {
+        if let Some(child) = Rc::get_mut(child)
+            && !matches!(
+                child,
+                Expression::Constant(_)
+                    | Expression::Pi
+                    | Expression::E
+                    | Expression::Variable
+                    | Expression::Symbol(_)
+            )
+        {
+            pending.push(std::mem::replace(child, Expression::Constant(0.0)));
+        }
+    };
+    match expr {
+        Expression::Constant(_)
+        | Expression::Pi
+        | Expression::E
+        | Expression::Variable
+        | Expression::Symbol(_) => {}
+        #[cfg(feature = "rational")]
+        Expression::Rational(..) => {}
+        Expression::Sum(a, b)
+        | Expression::Difference(a, b)
+        | Expression::Product(a, b)
+        | Expression::Quotient(a, b)
+        | Expression::PowerExpr(a, b) => {
+            detach(a);
+            detach(b);
+        }
+        Expression::Negate(a)
+        | Expression::Power(a, _)
+        | Expression::Root(a, _)
+        | Expression::Sin(a)
+        | Expression::Cos(a)
+        | Expression::Tan(a)
+        | Expression::Reciprocal(a)
+        | Expression::Sec(a)
+        | Expression::Csc(a)
+        | Expression::Cot(a)
+        | Expression::Atan(a)
+        | Expression::Sinh(a)
+        | Expression::Cosh(a)
+        | Expression::Abs(a)
+        | Expression::Sign(a)
+        | Expression::Exp(a)
+        | Expression::Ln(a)
+        | Expression::Derivative(a)
+        | Expression::Log(_, a) => detach(a),
+        Expression::Piecewise(branches) => {
+            for (_, branch) in branches {
+                detach(branch);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Expression {
+        Expression::parse(input).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        let scale = expected.abs().max(1.0);
+        assert!(
+            (actual - expected).abs() <= 1e-9 * scale,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn quotient_rule() {
+        let derivative = parse("x / (x - 1)").differentiate().unwrap();
+        for x in [-2.0, 0.0, 0.5, 3.0] {
+            assert_close(derivative.evaluate(x), -1.0 / ((x - 1.0) * (x - 1.0)));
+        }
+    }
+
+    #[test]
+    fn product_rule() {
+        let square =
+            Expression::Product(Rc::new(Expression::Variable), Rc::new(Expression::Variable));
+        assert_eq!(square.differentiate().unwrap().evaluate(3.0), 6.0);
+    }
+
+    #[test]
+    fn power_rule_with_a_non_variable_base() {
+        let derivative = parse("(x^2 - 4)^2").differentiate().unwrap();
+        assert_close(derivative.evaluate(1.0), -12.0);
+        assert_close(derivative.evaluate(2.0), 0.0);
+    }
+
+    #[test]
+    fn log2_derivative() {
+        let derivative = parse("log2(x)").differentiate().unwrap();
+        assert_close(derivative.evaluate(1.0), 1.0 / std::f64::consts::LN_2);
+        assert_close(derivative.evaluate(4.0), 0.25 / std::f64::consts::LN_2);
+    }
+
+    #[test]
+    fn structural_and_approximate_equality() {
+        assert_eq!(parse("x^2 + 1"), parse("(x^2) + 1"));
+        assert_ne!(parse("x + 1"), parse("1 + x"));
+        assert_ne!(parse("x^2"), parse("x * x"));
+
+        let samples = [-1.5, 0.25, 2.0, 10.0];
+        assert!(parse("x + 1").approx_equal(&parse("1 + x"), &samples, 1e-12));
+        assert!(parse("x^2").approx_equal(&parse("x * x"), &samples, 1e-12));
+        assert!(!parse("x").approx_equal(&parse("x + 1"), &samples, 1e-12));
+    }
+
+    #[test]
+    fn variable_helpers() {
+        assert!(parse("x + 1").contains_variable());
+        assert!(!parse("x + 1").is_constant());
+        assert!(parse("2 * pi + ln(3)").is_constant());
+        assert!(!parse("2 * pi + ln(3)").contains_variable());
+        assert!(Expression::Symbol('y').contains_variable());
+    }
+
+    #[test]
+    fn partial_derivatives() {
+        let expr = parse("x*y + x^2");
+        let vars = HashMap::from([('x', 3.0), ('y', 5.0)]);
+        // ∂/∂x = y + 2x and ∂/∂y = x
+        assert_eq!(
+            expr.differentiate_wrt('x').unwrap().evaluate_with(&vars),
+            11.0
+        );
+        assert_eq!(
+            expr.differentiate_wrt('y').unwrap().evaluate_with(&vars),
+            3.0
+        );
+    }
+
+    #[test]
+    fn taylor_coefficients_of_sin() {
+        let coefficients = parse("sin(x)").taylor(0.0, 3).unwrap();
+        let expected = [0.0, 1.0, 0.0, -1.0 / 6.0];
+        assert_eq!(coefficients.len(), expected.len());
+        for (actual, expected) in coefficients.into_iter().zip(expected) {
+            assert_close(actual, expected);
+        }
+    }
+
+    #[test]
+    fn derivatives_are_folded() {
+        let cases = [
+            ("x^2 - 4", "2 * x"),
+            ("x^3", "3 * x^2"),
+            ("2 * x + 1", "2"),
+            ("sin(x)", "cos(x)"),
+            ("7", "0"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input).differentiate().unwrap().to_string(), expected);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serde_round_trip() {
+        let expr = parse("(x^2 - 4) / (x - 2)");
+        let json = serde_json::to_string(&expr).unwrap();
+        let read: Expression = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, expr);
+    }
+
+    #[test]
+    fn evaluate_many() {
+        assert_eq!(
+            parse("x^2").evaluate_many(&[-2.0, -1.0, 0.0, 1.0, 2.0]),
+            [4.0, 1.0, 0.0, 1.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn second_derivative() {
+        assert_eq!(parse("x^3").differentiate_n(2).unwrap().evaluate(2.0), 12.0);
+        assert_eq!(parse("x^3").differentiate_n(0).unwrap(), parse("x^3"));
+    }
+
+    #[test]
+    fn variable_power_derivative() {
+        // d/dx(x^x) = x^x * (ln(x) + 1)
+        let derivative = parse("x^x").differentiate().unwrap();
+        assert_close(derivative.evaluate(1.0), 1.0);
+        assert_close(derivative.evaluate(2.0), 4.0 * (2f64.ln() + 1.0));
+    }
+
+    #[test]
+    fn products_and_quotients_of_any_factors() {
+        let product = parse("sin(x) * exp(x)").differentiate().unwrap();
+        assert_close(
+            product.evaluate(0.5),
+            0.5f64.exp() * (0.5f64.cos() + 0.5f64.sin()),
+        );
+        let quotient = parse("exp(x) / (x^2 + 1)").differentiate().unwrap();
+        let expected = 0.5f64.exp() * (1.25 - 1.0) / (1.25 * 1.25);
+        assert_close(quotient.evaluate(0.5), expected);
+    }
+
+    #[test]
+    fn substitute() {
+        let composed = parse("x^2").substitute(&parse("x + 1"));
+        assert_eq!(composed, parse("(x + 1)^2"));
+        assert_eq!(composed.evaluate(2.0), 9.0);
+    }
+
+    #[test]
+    fn shift() {
+        assert_eq!(parse("x - 2").shift(2.0).evaluate(0.0), 0.0);
+        assert_eq!(parse("x^2").shift(-1.0).evaluate(3.0), 4.0);
+    }
+
+    #[test]
+    fn iterates_over_every_node() {
+        let expr = parse("(x^2 - 4) / (x - 2)");
+        assert_eq!(expr.iter_nodes().count(), 8);
+        let variables = expr
+            .iter_nodes()
+            .filter(|node| matches!(node, Expression::Variable))
+            .count();
+        assert_eq!(variables, 2);
+        assert_eq!(expr.iter_nodes().next(), Some(&expr));
+    }
+
+    #[test]
+    fn depth_and_node_count() {
+        let cases = [
+            ("x", 1, 1),
+            ("sin(cos(x))", 3, 3),
+            ("(x^2 - 4) / (x - 2)", 4, 8),
+            ("x + 1 + 2 + 3", 4, 7),
+        ];
+        for (input, depth, node_count) in cases {
+            let expr = parse(input);
+            assert_eq!(expr.depth(), depth, "{}", input);
+            assert_eq!(expr.node_count(), node_count, "{}", input);
+        }
+    }
+
+    #[test]
+    fn constant_value() {
+        let sum = Expression::Sum(
+            Rc::new(Expression::Constant(2.0)),
+            Rc::new(Expression::Constant(3.0)),
+        );
+        assert_eq!(sum.constant_value(), Some(5.0));
+        assert_eq!(parse("x + 1").constant_value(), None);
+    }
+
+    #[test]
+    fn derivatives_match_central_differences() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let mut rng = StdRng::seed_from_u64(56);
+        let mut compared = 0;
+        for _ in 0..2_000 {
+            let expr = Expression::random(4, &mut rng);
+            let Ok(derivative) = expr.differentiate() else {
+                continue;
+            };
+            for x in [-2.3, -0.7, 0.4, 1.9, 3.1] {
+                // Large intermediate values make the finite difference meaningless, as
+                // for `sec(sinh(y))` with `y` far from zero.
+                if expr.iter_nodes().any(|node| node.evaluate(x).abs() > 1e6) {
+                    continue;
+                }
+                let symbolic = derivative.evaluate(x);
+                let h = 1e-5 * f64::max(x.abs(), 1.0);
+                let numeric = expr.numeric_derivative(x, h);
+                // A difference that changes with the step is straddling a kink, a jump
+                // or a singularity rather than approximating the slope.
+                let refined = expr.numeric_derivative(x, h / 2.0);
+                if !symbolic.is_finite() || !numeric.is_finite() || !refined.is_finite() {
+                    continue;
+                }
+                if (numeric - refined).abs() > 1e-6 * numeric.abs().max(1.0) {
+                    continue;
+                }
+                let scale = symbolic.abs().max(numeric.abs()).max(1.0);
+                assert!(
+                    (symbolic - numeric).abs() <= 1e-4 * scale,
+                    "d/dx({}) at {} is {}, but a central difference gives {}",
+                    expr,
+                    x,
+                    symbolic,
+                    numeric
+                );
+                compared += 1;
+            }
+        }
+        assert!(compared > 1_000, "only {} points were compared", compared);
+    }
+
+    #[test]
+    fn evaluate_checked() {
+        assert_eq!(
+            parse("ln(x)").evaluate_checked(-1.0),
+            Err(LhopitalError::DomainError(String::from("ln(x)")))
+        );
+        assert_eq!(parse("ln(x)").evaluate_checked(1.0), Ok(0.0));
+    }
+
+    #[test]
+    fn exact_constants() {
+        assert_eq!(Expression::E.evaluate(0.0), std::f64::consts::E);
+        assert_eq!(Expression::Pi.evaluate(0.0), std::f64::consts::PI);
+        assert_eq!(parse("e"), Expression::E);
+        assert_eq!(Expression::E.to_string(), "e");
+    }
+
+    #[test]
+    fn clones_share_their_children() {
+        let expr = parse("sin(x^2) / (x - 2)");
+        let clone = expr.clone();
+        let (Expression::Quotient(a, b), Expression::Quotient(c, d)) = (&expr, &clone) else {
+            unreachable!();
+        };
+        assert!(Rc::ptr_eq(a, c) && Rc::ptr_eq(b, d));
+        assert_eq!(clone.evaluate(3.0), 9f64.sin());
+        assert_eq!(clone.differentiate(), expr.differentiate());
+    }
+
+    #[test]
+    fn structurally_equal_expressions_share_a_map_entry() {
+        let mut counts = HashMap::new();
+        *counts.entry(parse("(x^2 - 4) / (x - 2)")).or_insert(0) += 1;
+        *counts.entry(parse("(x^2-4)/(x-2)")).or_insert(0) += 1;
+        *counts.entry(parse("(x^2 - 4) / (x - 3)")).or_insert(0) += 1;
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&parse("(x^2 - 4) / (x - 2)")], 2);
+    }
+
+    #[test]
+    fn reciprocal_trig_derivatives() {
+        let x: f64 = 0.5;
+        let cases = [
+            ("sec(x)", x.tan() / x.cos()),
+            ("csc(x)", -1.0 / (x.sin() * x.tan())),
+            ("cot(x)", -1.0 / (x.sin() * x.sin())),
+        ];
+        for (input, expected) in cases {
+            assert_close(parse(input).differentiate().unwrap().evaluate(x), expected);
+        }
+    }
+
+    #[test]
+    fn derivative_at() {
+        assert_eq!(parse("x^3").derivative_at(2.0), 12.0);
+        assert!(parse("sign(x)").derivative_at(2.0).is_nan());
+    }
+
+    #[test]
+    fn numeric_and_symbolic_derivatives_agree() {
+        let expr = parse("sin(x) * exp(x) / (x^2 + 1)");
+        for x in [-1.5, 0.0, 0.5, 2.0] {
+            assert!((expr.numeric_derivative(x, 0.00001) - expr.derivative_at(x)).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn zero_and_one_predicates() {
+        assert!(Expression::zero().is_zero() && !Expression::zero().is_one());
+        assert!(Expression::one().is_one() && !Expression::one().is_zero());
+        assert!(parse("2 - 2").is_zero());
+        assert!(parse("2 / 2").is_one());
+        assert!(parse("ln(e)").is_one());
+        assert!(!parse("x - x").is_zero());
+        assert!(!parse("x / x").is_one());
+    }
+
+    #[test]
+    fn two_piece_function() {
+        let expr = Expression::piecewise([
+            (Condition::Less(1.0), Expression::var().pow(2.0)),
+            (
+                Condition::GreaterOrEqual(1.0),
+                Expression::c(2.0) * Expression::var(),
+            ),
+        ]);
+        assert_eq!(expr.evaluate(0.5), 0.25);
+        assert_eq!(expr.evaluate(1.0), 2.0);
+        assert_eq!(expr.evaluate(1.5), 3.0);
+        assert_close(expr.evaluate(1.0 - 1e-12), 1.0);
+    }
+
+    #[test]
+    fn reciprocal_derivative() {
+        let reciprocal = Expression::Reciprocal(Rc::new(Expression::Variable));
+        let derivative = reciprocal.differentiate().unwrap();
+        for x in [-3.0, -0.5, 0.25, 2.0] {
+            assert_close(derivative.evaluate(x), -1.0 / (x * x));
+        }
+    }
+
+    #[test]
+    fn map_constants() {
+        let doubled = parse("2*x + 3").map_constants(|c| 2.0 * c);
+        assert_eq!(doubled, parse("4*x + 6"));
+        assert_eq!(
+            parse("x^2 + pi").map_constants(|c| 2.0 * c),
+            parse("x^2 + pi")
+        );
+    }
+
+    #[test]
+    fn zeroth_power_derivative() {
+        let derivative = Expression::Variable.pow(0.0).differentiate().unwrap();
+        assert!(derivative.is_zero(), "{}", derivative);
+        assert!(
+            !derivative
+                .iter_nodes()
+                .any(|node| matches!(node, Expression::Power(_, exp) if *exp == -1.0))
+        );
+    }
+
+    #[test]
+    fn partial_differentiation() {
+        let derivative = parse("x^2 + sign(x)").differentiate_partial();
+        let Expression::Sum(supported, unsupported) = &derivative else {
+            panic!("expected a sum, got {}", derivative);
+        };
+        assert_close(supported.evaluate(3.0), 6.0);
+        assert_eq!(
+            **unsupported,
+            Expression::Derivative(Rc::new(parse("sign(x)")))
+        );
+    }
+
+    #[test]
+    fn custom_infinity_proxy() {
+        let reciprocal = parse("1 / x");
+        assert_eq!(reciprocal.evaluate_at_infinity(1.0, 1000.0), 0.0);
+        assert_eq!(reciprocal.evaluate_at_infinity(-1.0, 100000000.0), 0.0);
+        assert_eq!(
+            parse("ln(x)").evaluate_at_infinity(1.0, 1000.0),
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn explains_the_chain_rule() {
+        let (_, rules) = parse("sin(x^2)").differentiate_explained().unwrap();
+        assert!(rules.iter().any(|rule| rule == "chain rule"), "{:?}", rules);
+        assert!(rules.iter().any(|rule| rule == "power rule"), "{:?}", rules);
+    }
+
+    #[test]
+    fn integer_powers_use_powi() {
+        let x: f64 = 1.00002;
+        assert_ne!(x.powi(5), x.powf(5.0));
+        assert_eq!(parse("x^5").evaluate(x), x.powi(5));
+        assert_eq!(parse("x^2.5").evaluate(x), x.powf(2.5));
+    }
+
+    #[test]
+    fn scale_variable() {
+        assert_eq!(parse("x^2").scale_variable(2.0).evaluate(1.0), 4.0);
+        let step = Expression::piecewise([
+            (Condition::Less(1.0), Expression::zero()),
+            (Condition::GreaterOrEqual(1.0), Expression::one()),
+        ]);
+        let flipped = step.scale_variable(-2.0);
+        assert_eq!(flipped.evaluate(-1.0), 1.0);
+        assert_eq!(flipped.evaluate(0.0), 0.0);
+    }
+
+    #[test]
+    fn structural_equality_within_tolerance() {
+        let a = parse("2 * x^3 + 0.1");
+        let b = parse("2.0000000001 * x^3.0000000001 + 0.1");
+        assert_ne!(a, b);
+        assert!(a.structurally_eq_within(&b, 1e-9));
+        assert!(!a.structurally_eq_within(&b, 1e-12));
+        assert!(!a.structurally_eq_within(&parse("2 * x^3 - 0.1"), 1e-9));
+    }
+
+    #[test]
+    fn first_power_derivative() {
+        let power = Expression::Power(Rc::new(parse("x + 1")), 1.0);
+        assert_eq!(
+            power.differentiate().unwrap(),
+            parse("x + 1").differentiate().unwrap()
+        );
+        assert_eq!(power.derivative_at(-1.0), 1.0);
+    }
+
+    #[test]
+    fn non_finite_operands() {
+        let inf = Expression::Constant(f64::INFINITY);
+        let nan = Expression::Constant(f64::NAN);
+        let one = Expression::one();
+        let zero = Expression::zero();
+        let cases = [
+            (inf.clone() + one.clone(), f64::INFINITY),
+            (one.clone() / inf.clone(), 0.0),
+            (inf.clone() * 2.0, f64::INFINITY),
+            (-inf.clone(), f64::NEG_INFINITY),
+            (Expression::Exp(Rc::new(-inf.clone())), 0.0),
+            (Expression::Ln(Rc::new(zero.clone())), f64::NEG_INFINITY),
+            (inf.clone() - inf.clone(), f64::NAN),
+            (zero.clone() * inf.clone(), f64::NAN),
+            (inf.clone() / inf.clone(), f64::NAN),
+            (nan.clone() + one.clone(), f64::NAN),
+            (nan.clone().pow(0.0), f64::NAN),
+            (
+                Expression::PowerExpr(Rc::new(one.clone()), Rc::new(nan.clone())),
+                f64::NAN,
+            ),
+            (Expression::Sin(Rc::new(inf.clone())), f64::NAN),
+        ];
+        for (expr, expected) in cases {
+            let value = expr.evaluate(0.0);
+            assert!(
+                value == expected || (value.is_nan() && expected.is_nan()),
+                "{:?} gave {}",
+                expr,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn lazy_derivative() {
+        let derivative = Expression::Derivative(Rc::new(parse("x^3")));
+        assert_eq!(derivative.evaluate(2.0), 12.0);
+        let second = derivative.differentiate().unwrap();
+        // d/dx(d/dx(x^3)) is 6x, which is also 12 at 2.
+        assert_eq!(second.evaluate(2.0), 12.0);
+        assert_eq!(second.evaluate(1.0), 6.0);
+        assert!(
+            parse("x^2 + sign(x)")
+                .differentiate_partial()
+                .evaluate(1.0)
+                .is_nan()
+        );
+    }
+
+    #[test]
+    fn roots_near() {
+        assert_eq!(parse("x - 2").roots_near(2.0, 1.0, 100), vec![2.0]);
+        let roots = parse("x^2 - 2").roots_near(0.0, 2.0, 100);
+        assert_eq!(roots.len(), 2);
+        assert_close(roots[0], -std::f64::consts::SQRT_2);
+        assert_close(roots[1], std::f64::consts::SQRT_2);
+    }
+
+    #[test]
+    fn three_factor_product() {
+        let derivative = parse("x * x * x").differentiate().unwrap();
+        assert_eq!(derivative.expand(), parse("3 * x^2"));
+        let mixed = parse("sin(x) * x * (exp(x) * x^2)");
+        let derivative = mixed.differentiate().unwrap();
+        for x in [-1.0, 0.5, 2.0] {
+            assert!((derivative.evaluate(x) - mixed.numeric_derivative(x, 0.00001)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn to_function() {
+        fn sample(f: impl Fn(f64) -> f64, points: &[f64]) -> Vec<f64> {
+            points.iter().map(|&x| f(x)).collect()
+        }
+        let expr = parse("x^2 - 4");
+        assert_eq!(
+            sample(expr.to_function(), &[0.0, 1.0, 2.0]),
+            vec![-4.0, -3.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn domain_check() {
+        assert_eq!(
+            parse("ln(x)").domain_check(-1.0),
+            Err(LhopitalError::DomainError(String::from("ln(x)")))
+        );
+        assert!(parse("1 / (x - 2)").domain_check(2.0).is_err());
+        assert_eq!(parse("1 / (x - 2)").domain_check(1.0), Ok(()));
+    }
+
+    #[test]
+    fn clamp_exponents() {
+        assert_eq!(parse("x^1000").clamp_exponents(50.0), parse("x^50"));
+        assert_eq!(
+            parse("x^-1000 + x^3").clamp_exponents(50.0),
+            parse("x^-50 + x^3")
+        );
+        assert_eq!(
+            parse("x^1000").check_exponents(50.0),
+            Err(LhopitalError::ExponentTooLarge { limit: 50.0 })
+        );
+    }
+
+    // The position of `expr`'s variant in the declaration. The match has no wildcard
+    // arm, so a new variant does not compile until it is numbered here, and then
+    // `every_variant_is_handled` fails until it is listed there too.
+    fn variant_index(expr: &Expression) -> usize {
+        match expr {
+            Expression::Constant(_) => 0,
+            Expression::Pi => 1,
+            Expression::E => 2,
+            Expression::Variable => 3,
+            Expression::Symbol(_) => 4,
+            Expression::Sum(..) => 5,
+            Expression::Difference(..) => 6,
+            Expression::Negate(_) => 7,
+            Expression::Product(..) => 8,
+            Expression::Quotient(..) => 9,
+            Expression::Reciprocal(_) => 10,
+            Expression::Power(..) => 11,
+            Expression::PowerExpr(..) => 12,
+            Expression::Root(..) => 13,
+            Expression::Sin(_) => 14,
+            Expression::Cos(_) => 15,
+            Expression::Tan(_) => 16,
+            Expression::Sec(_) => 17,
+            Expression::Csc(_) => 18,
+            Expression::Cot(_) => 19,
+            Expression::Atan(_) => 20,
+            Expression::Sinh(_) => 21,
+            Expression::Cosh(_) => 22,
+            Expression::Abs(_) => 23,
+            Expression::Sign(_) => 24,
+            Expression::Exp(_) => 25,
+            Expression::Ln(_) => 26,
+            Expression::Log(..) => 27,
+            Expression::Derivative(_) => 28,
+            Expression::Piecewise(_) => 29,
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => 30,
+        }
+    }
+
+    #[test]
+    fn every_variant_is_handled() {
+        let variant_count = if cfg!(feature = "rational") { 31 } else { 30 };
+        let x = || Rc::new(Expression::Variable);
+        let variants = vec![
+            Expression::Constant(2.0),
+            Expression::Pi,
+            Expression::E,
+            Expression::Variable,
+            Expression::Symbol('a'),
+            Expression::Sum(x(), x()),
+            Expression::Difference(x(), x()),
+            Expression::Negate(x()),
+            Expression::Product(x(), x()),
+            Expression::Quotient(x(), x()),
+            Expression::Reciprocal(x()),
+            Expression::Power(x(), 3.0),
+            Expression::PowerExpr(x(), x()),
+            Expression::Root(x(), 2.0),
+            Expression::Sin(x()),
+            Expression::Cos(x()),
+            Expression::Tan(x()),
+            Expression::Sec(x()),
+            Expression::Csc(x()),
+            Expression::Cot(x()),
+            Expression::Atan(x()),
+            Expression::Sinh(x()),
+            Expression::Cosh(x()),
+            Expression::Abs(x()),
+            Expression::Sign(x()),
+            Expression::Exp(x()),
+            Expression::Ln(x()),
+            Expression::Log(2.0, x()),
+            Expression::Derivative(Rc::new(Expression::Power(x(), 2.0))),
+            Expression::piecewise([
+                (Condition::Less(0.0), -Expression::var()),
+                (Condition::GreaterOrEqual(0.0), Expression::var()),
+            ]),
+        ];
+        #[cfg(feature = "rational")]
+        let variants = [variants, vec![Expression::Rational(1, 3)]].concat();
+
+        let mut indices: Vec<_> = variants.iter().map(variant_index).collect();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..variant_count).collect::<Vec<_>>());
+
+        for expr in &variants {
+            assert!(!expr.to_string().is_empty(), "{:?}", expr);
+            assert!(!expr.to_latex().is_empty(), "{:?}", expr);
+            assert_eq!(Expression::from_sexpr(&expr.to_sexpr()).as_ref(), Ok(expr));
+            let (value, simplified) = (expr.evaluate(0.7), expr.simplify().evaluate(0.7));
+            assert!(
+                (value - simplified).abs() < 1e-12 || (value.is_nan() && simplified.is_nan()),
+                "{:?}",
+                expr
+            );
+            match expr.differentiate() {
+                Ok(derivative) => {
+                    assert!(derivative.evaluate(0.7).is_finite(), "{:?}", expr);
+                }
+                Err(error) => {
+                    assert!(matches!(
+                        error,
+                        LhopitalError::DifferentiationUnsupported(_)
+                    ))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate_iter_matches_evaluate() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let same = |a: f64, b: f64| a == b || (a.is_nan() && b.is_nan());
+        let points = [-2.5, -1.0, 0.0, 0.3, 1.0, 4.0];
+        for input in [
+            "x^2 - 4",
+            "sin(x) / x",
+            "exp(-x^2) * ln(abs(x))",
+            "sqrt(x) + atan(x)",
+        ] {
+            let expr = parse(input);
+            for x in points {
+                assert!(
+                    same(expr.evaluate_iter(x), expr.evaluate(x)),
+                    "{} at {}",
+                    input,
+                    x
+                );
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(136);
+        for _ in 0..2000 {
+            let expr = Expression::random(7, &mut rng);
+            for x in points {
+                assert!(
+                    same(expr.evaluate_iter(x), expr.evaluate(x)),
+                    "{} at {}",
+                    expr,
+                    x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate_iter_handles_deep_trees() {
+        let chain = (1..1_000_000).fold(Expression::one(), |sum, _| sum + 1.0);
+        assert_eq!(chain.evaluate_iter(0.0), 1_000_000.0);
+    }
+}
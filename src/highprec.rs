@@ -0,0 +1,79 @@
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::Expression;
+
+impl Expression {
+    /// Evaluates the expression at `x` in arbitrary-precision decimal arithmetic, for
+    /// points too close to a singularity for an `f64` to tell apart from it.
+    ///
+    /// Only arithmetic is supported: constants, `x`, sums, differences, negations,
+    /// products, quotients and integer powers. Any other node, such as `π`, `sin` or
+    /// a fractional power, has no exact decimal value and gives `None`, as does a
+    /// division by zero. Constants are converted exactly from their `f64` value, and
+    /// quotients and negative powers are rounded to the `bigdecimal` crate's default
+    /// precision of 100 digits.
+    pub fn evaluate_bigdecimal(&self, x: &BigDecimal) -> Option<BigDecimal> {
+        Some(match self {
+            Expression::Constant(c) => BigDecimal::try_from(*c).ok()?,
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => BigDecimal::from(*n) / BigDecimal::from(*d),
+            Expression::Variable => x.clone(),
+            Expression::Sum(a, b) => a.evaluate_bigdecimal(x)? + b.evaluate_bigdecimal(x)?,
+            Expression::Difference(a, b) => a.evaluate_bigdecimal(x)? - b.evaluate_bigdecimal(x)?,
+            Expression::Negate(a) => -a.evaluate_bigdecimal(x)?,
+            Expression::Product(a, b) => a.evaluate_bigdecimal(x)? * b.evaluate_bigdecimal(x)?,
+            Expression::Quotient(a, b) => {
+                let den = b.evaluate_bigdecimal(x)?;
+                if den.is_zero() {
+                    return None;
+                }
+                a.evaluate_bigdecimal(x)? / den
+            }
+            Expression::Power(base, exp) if exp.fract() == 0.0 => {
+                let base = base.evaluate_bigdecimal(x)?;
+                // `powi` returns zero itself for a zero base, whatever the exponent.
+                if base.is_zero() && *exp < 0.0 {
+                    return None;
+                } else if *exp == 0.0 {
+                    return Some(BigDecimal::from(1));
+                }
+                base.powi(*exp as i64)
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn near_a_root() {
+        let expr = Expression::parse("x^2 - 4").unwrap();
+        let x = BigDecimal::from_str("2.000000000000000000000000000001").unwrap();
+        let expected =
+            BigDecimal::from_str("0.000000000000000000000000000004000000000000000000000000000001")
+                .unwrap();
+        assert_eq!(expr.evaluate_bigdecimal(&x), Some(expected));
+        // The same point as an `f64` is 2 itself.
+        assert_eq!(expr.evaluate(2.000000000000000000000000000001), 0.0);
+    }
+
+    #[test]
+    fn unsupported_nodes() {
+        let x = BigDecimal::from(2);
+        assert_eq!(
+            Expression::parse("sin(x)").unwrap().evaluate_bigdecimal(&x),
+            None
+        );
+        assert_eq!(
+            Expression::parse("1 / (x - 2)")
+                .unwrap()
+                .evaluate_bigdecimal(&x),
+            None
+        );
+    }
+}
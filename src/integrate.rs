@@ -0,0 +1,168 @@
+use std::rc::Rc;
+
+use crate::builder::monomial;
+use crate::{Expression, LhopitalError};
+
+impl Expression {
+    /// An antiderivative with respect to `x`, without a constant of integration.
+    ///
+    /// Covers polynomials: sums and differences term by term, constant factors and
+    /// divisors, and powers of `x` by the reverse power rule `x^n -> x^(n+1)/(n+1)`.
+    /// `x^-1` and `c/x` integrate to `ln(abs(x))`. Any other term gives
+    /// [`LhopitalError::IntegrationUnsupported`].
+    pub fn integrate(&self) -> Result<Expression, LhopitalError> {
+        self.antiderivative(1.0).map(|integral| integral.simplify())
+    }
+
+    // An antiderivative of `scale` times the expression. Numeric factors and divisors
+    // are passed down in `scale` rather than kept outside, so that they fold into the
+    // coefficient of each power of `x` and `3*x^2` integrates to `x^3`.
+    fn antiderivative(&self, scale: f64) -> Result<Expression, LhopitalError> {
+        let ln_abs_x = || Expression::Ln(Rc::new(Expression::Abs(Rc::new(Expression::Variable))));
+        let scaled = |expr: Expression| {
+            if scale == 1.0 {
+                expr
+            } else {
+                Expression::Constant(scale) * expr
+            }
+        };
+        // ∫c dx = c*x for anything that does not depend on x
+        if !depends_on_x(self) {
+            return Ok(scaled(self.clone() * Expression::Variable));
+        }
+        match self {
+            // ∫(f+g) dx = ∫f dx + ∫g dx
+            Expression::Sum(a, b) => Ok(a.antiderivative(scale)? + b.antiderivative(scale)?),
+            // ∫(f-g) dx = ∫f dx - ∫g dx
+            Expression::Difference(a, b) => Ok(a.antiderivative(scale)? - b.antiderivative(scale)?),
+            // ∫-f dx = -∫f dx
+            Expression::Negate(a) => Ok(-a.antiderivative(scale)?),
+            // ∫c*f dx = c * ∫f dx
+            Expression::Product(a, b) if let Expression::Constant(c) = **b => {
+                a.antiderivative(scale * c)
+            }
+            Expression::Product(a, b) if let Expression::Constant(c) = **a => {
+                b.antiderivative(scale * c)
+            }
+            Expression::Product(a, b) if !depends_on_x(b) => {
+                Ok(a.antiderivative(scale)? * (**b).clone())
+            }
+            Expression::Product(a, b) if !depends_on_x(a) => {
+                Ok((**a).clone() * b.antiderivative(scale)?)
+            }
+            // ∫c/x dx = c * ln|x|
+            Expression::Quotient(a, b)
+                if matches!(**b, Expression::Variable) && !depends_on_x(a) =>
+            {
+                Ok(scaled((**a).clone() * ln_abs_x()))
+            }
+            // ∫1/x dx = ln|x|
+            Expression::Reciprocal(a) if matches!(**a, Expression::Variable) => {
+                Ok(scaled(ln_abs_x()))
+            }
+            // ∫f/c dx = (∫f dx) / c
+            Expression::Quotient(a, b)
+                if let Expression::Constant(c) = **b
+                    && c != 0.0 =>
+            {
+                a.antiderivative(scale / c)
+            }
+            Expression::Quotient(a, b) if !depends_on_x(b) => {
+                Ok(a.antiderivative(scale)? / (**b).clone())
+            }
+            // ∫x dx = x^2/2
+            Expression::Variable => Ok(power_term(2.0, scale)),
+            // ∫x^-1 dx = ln|x|
+            Expression::Power(base, exp)
+                if matches!(**base, Expression::Variable) && *exp == -1.0 =>
+            {
+                Ok(scaled(ln_abs_x()))
+            }
+            // ∫x^n dx = x^(n+1)/(n+1)
+            Expression::Power(base, exp) if matches!(**base, Expression::Variable) => {
+                Ok(power_term(exp + 1.0, scale))
+            }
+            other => Err(LhopitalError::IntegrationUnsupported(other.to_string())),
+        }
+    }
+}
+
+// `scale * x^n / n`, with `scale / n` written as one coefficient when it or its
+// reciprocal is a whole number, so `2*x` integrates to `x^2` and `x^2/4` to `x^3 / 12`.
+fn power_term(degree: f64, scale: f64) -> Expression {
+    let coefficient = scale / degree;
+    if coefficient.fract() == 0.0 {
+        return monomial(degree, coefficient);
+    }
+    if coefficient > 0.0 && coefficient.recip().fract() == 0.0 {
+        return Expression::Variable.pow(degree) / coefficient.recip();
+    }
+    let quotient = Expression::Variable.pow(degree) / degree;
+    if scale == 1.0 {
+        quotient
+    } else {
+        Expression::Constant(scale) * quotient
+    }
+}
+
+// Symbols are constants as far as integration with respect to `x` is concerned.
+fn depends_on_x(expr: &Expression) -> bool {
+    expr.iter_nodes()
+        .any(|node| matches!(node, Expression::Variable | Expression::Piecewise(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integral(input: &str) -> String {
+        Expression::parse(input)
+            .unwrap()
+            .integrate()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn constant() {
+        assert_eq!(integral("5"), "5 * x");
+    }
+
+    #[test]
+    fn linear_term() {
+        assert_eq!(integral("2 * x + 1"), "x^2 + x");
+        assert_eq!(integral("x"), "x^2 / 2");
+    }
+
+    #[test]
+    fn folds_coefficients() {
+        assert_eq!(integral("3 * x^2"), "x^3");
+        assert_eq!(integral("x^3 - 2 * x + 4"), "x^4 / 4 - x^2 + 4 * x");
+        assert_eq!(integral("2 * x^2"), "2 * x^3 / 3");
+        assert_eq!(integral("x^2 / 4"), "x^3 / 12");
+    }
+
+    #[test]
+    fn recovers_x_cubed_from_its_derivative() {
+        let cube = Expression::parse("x^3").unwrap();
+        assert_eq!(cube.differentiate().unwrap().integrate().unwrap(), cube);
+    }
+
+    #[test]
+    fn derivative_of_integral_is_the_original() {
+        for input in [
+            "5",
+            "2 * x + 1",
+            "3 * x^2",
+            "x^3 - 2 * x + 4",
+            "x^2 / 4 - 7 / x",
+        ] {
+            let expr = Expression::parse(input).unwrap();
+            let round_trip = expr.integrate().unwrap().differentiate().unwrap();
+            for x in [-2.5, -0.5, 0.75, 3.0] {
+                let (want, got) = (expr.evaluate(x), round_trip.evaluate(x));
+                assert!((want - got).abs() < 1e-9, "{input} at {x}: {got} != {want}");
+            }
+        }
+    }
+}
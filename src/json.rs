@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{DEFAULT_MAX_ITERATIONS, Expression, LimitPoint, lhopital_solve_traced};
+
+#[derive(Deserialize)]
+struct Request {
+    num: String,
+    den: String,
+    at: Point,
+    max_iter: Option<u32>,
+}
+
+// The limit point as a JSON number, or as a string in the syntax `LimitPoint` parses,
+// since JSON has no infinities.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Point {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response {
+    Solved {
+        #[serde(serialize_with = "number")]
+        value: f64,
+        steps: Vec<Step>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Step {
+    numerator: String,
+    denominator: String,
+    #[serde(serialize_with = "number")]
+    numerator_value: f64,
+    #[serde(serialize_with = "number")]
+    denominator_value: f64,
+    form: Option<String>,
+}
+
+// JSON has no infinities or NaN, so those are written as the strings `"inf"`, `"-inf"`
+// and `"NaN"` instead.
+fn number<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.is_finite() {
+        serializer.serialize_f64(*value)
+    } else {
+        serializer.serialize_str(&value.to_string())
+    }
+}
+
+/// Solves a limit described by a JSON request and describes the outcome as JSON, for
+/// callers such as browsers that cannot use the Rust API directly.
+///
+/// The request is an object like `{"num": "sin(x)", "den": "x", "at": 0}`, with an
+/// optional `max_iter` defaulting to [`DEFAULT_MAX_ITERATIONS`]. `at` is a number or
+/// a string such as `"inf"` or `"0+"`. The response is either
+/// `{"value": ..., "steps": [...]}`, each step giving the quotient, its value at the
+/// point and the indeterminate form found there, or `{"error": "..."}`. Infinite and
+/// NaN values are written as the strings `"inf"`, `"-inf"` and `"NaN"`.
+///
+/// With the `wasm` feature this is exported to JavaScript through `wasm-bindgen`.
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+pub fn solve_json(input: &str) -> String {
+    let response = solve_request(input).unwrap_or_else(|error| Response::Failed { error });
+    serde_json::to_string(&response).expect("responses always serialize")
+}
+
+fn solve_request(input: &str) -> Result<Response, String> {
+    let request: Request = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let numerator = Expression::parse(&request.num).map_err(|e| e.to_string())?;
+    let denominator = Expression::parse(&request.den).map_err(|e| e.to_string())?;
+    let at = match request.at {
+        Point::Number(at) => LimitPoint::from(at),
+        Point::Text(at) => at.parse::<LimitPoint>().map_err(|e| e.to_string())?,
+    };
+    let max_iterations = request.max_iter.unwrap_or(DEFAULT_MAX_ITERATIONS);
+
+    let result = lhopital_solve_traced(&numerator, &denominator, at, max_iterations)
+        .map_err(|e| e.to_string())?;
+    Ok(Response::Solved {
+        value: result.value,
+        steps: result
+            .iterations
+            .iter()
+            .map(|step| Step {
+                numerator: step.numerator.to_string(),
+                denominator: step.denominator.to_string(),
+                numerator_value: step.numerator_value,
+                denominator_value: step.denominator_value,
+                form: step.form.map(|form| form.to_string()),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::LhopitalError;
+
+    fn solve(request: Value) -> Value {
+        serde_json::from_str(&solve_json(&request.to_string())).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let response = solve(json!({"num": "sin(x)", "den": "x", "at": 0}));
+        assert_eq!(
+            response,
+            json!({
+                "value": 1.0,
+                "steps": [
+                    {
+                        "numerator": "sin(x)",
+                        "denominator": "x",
+                        "numerator_value": 0.0,
+                        "denominator_value": 0.0,
+                        "form": "0/0",
+                    },
+                    {
+                        "numerator": "cos(x)",
+                        "denominator": "1",
+                        "numerator_value": 1.0,
+                        "denominator_value": 1.0,
+                        "form": null,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn infinities_and_errors() {
+        let response = solve(json!({"num": "1", "den": "x - 2", "at": "2+"}));
+        assert_eq!(response["value"], "inf");
+        let response = solve(json!({"num": "sin(x", "den": "x", "at": 0}));
+        assert_eq!(response, json!({"error": "Unexpected end of input."}));
+        let response = solve(json!({"num": "1 - cos(x)", "den": "x^2", "at": 0, "max_iter": 1}));
+        assert_eq!(
+            response["error"],
+            LhopitalError::MaxIterationsExceeded.to_string()
+        );
+    }
+}
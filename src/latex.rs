@@ -0,0 +1,251 @@
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::display::{ATOM, PRODUCT, SUM, UNARY};
+use crate::{Condition, Expression, LhopitalResult, LimitPoint};
+
+impl Expression {
+    /// Renders the expression as LaTeX, e.g. `\frac{x^{2} - 4}{x - 2}`.
+    pub fn to_latex(&self) -> String {
+        let mut out = String::new();
+        self.write_latex(&mut out);
+        out
+    }
+
+    fn write_latex(&self, out: &mut String) {
+        match self {
+            Expression::Constant(c) => write_number(out, *c),
+            Expression::Pi => out.push_str("\\pi"),
+            Expression::E => out.push('e'),
+            Expression::Variable => out.push('x'),
+            Expression::Symbol(name) => out.push(*name),
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => {
+                if *n < 0 {
+                    out.push('-');
+                }
+                write!(out, "\\frac{{{}}}{{{}}}", n.unsigned_abs(), d).unwrap();
+            }
+            Expression::Sum(a, b) => {
+                a.write_latex_operand(out, SUM);
+                out.push_str(" + ");
+                b.write_latex_operand(out, SUM);
+            }
+            Expression::Difference(a, b) => {
+                a.write_latex_operand(out, SUM);
+                out.push_str(" - ");
+                b.write_latex_operand(out, PRODUCT);
+            }
+            Expression::Negate(a) => {
+                out.push('-');
+                match **a {
+                    Expression::Negate(_) => a.write_latex_parenthesized(out),
+                    Expression::Constant(c) if c < 0.0 => a.write_latex_parenthesized(out),
+                    _ => a.write_latex_operand(out, UNARY),
+                }
+            }
+            Expression::Product(a, b) => {
+                a.write_latex_operand(out, PRODUCT);
+                out.push_str(" \\cdot ");
+                b.write_latex_operand(out, PRODUCT);
+            }
+            Expression::Quotient(a, b) => {
+                out.push_str("\\frac{");
+                a.write_latex(out);
+                out.push_str("}{");
+                b.write_latex(out);
+                out.push('}');
+            }
+            Expression::Power(base, exp) => {
+                base.write_latex_operand(out, ATOM);
+                out.push_str("^{");
+                write_number(out, *exp);
+                out.push('}');
+            }
+            Expression::PowerExpr(base, exp) => {
+                base.write_latex_operand(out, ATOM);
+                out.push_str("^{");
+                exp.write_latex(out);
+                out.push('}');
+            }
+            Expression::Root(a, n) => {
+                out.push_str("\\sqrt");
+                if *n != 2.0 {
+                    out.push('[');
+                    write_number(out, *n);
+                    out.push(']');
+                }
+                out.push('{');
+                a.write_latex(out);
+                out.push('}');
+            }
+            Expression::Sin(a) => write_function(out, "\\sin", a),
+            Expression::Cos(a) => write_function(out, "\\cos", a),
+            Expression::Tan(a) => write_function(out, "\\tan", a),
+            Expression::Reciprocal(a) => {
+                out.push_str("\\frac{1}{");
+                a.write_latex(out);
+                out.push('}');
+            }
+            Expression::Sec(a) => write_function(out, "\\sec", a),
+            Expression::Csc(a) => write_function(out, "\\csc", a),
+            Expression::Cot(a) => write_function(out, "\\cot", a),
+            Expression::Atan(a) => write_function(out, "\\arctan", a),
+            Expression::Sinh(a) => write_function(out, "\\sinh", a),
+            Expression::Cosh(a) => write_function(out, "\\cosh", a),
+            Expression::Abs(a) => {
+                out.push_str("\\left|");
+                a.write_latex(out);
+                out.push_str("\\right|");
+            }
+            Expression::Sign(a) => write_function(out, "\\operatorname{sgn}", a),
+            Expression::Exp(a) => {
+                out.push_str("e^{");
+                a.write_latex(out);
+                out.push('}');
+            }
+            Expression::Ln(a) => write_function(out, "\\ln", a),
+            Expression::Derivative(a) => write_function(out, "\\frac{d}{dx}", a),
+            Expression::Log(base, a) => {
+                out.push_str("\\log_{");
+                write_number(out, *base);
+                out.push('}');
+                a.write_latex_parenthesized(out);
+            }
+            Expression::Piecewise(branches) => {
+                out.push_str("\\begin{cases} ");
+                for (i, (condition, branch)) in branches.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(" \\\\ ");
+                    }
+                    let (operator, bound) = match *condition {
+                        Condition::Less(c) => ("<", c),
+                        Condition::LessOrEqual(c) => ("\\leq", c),
+                        Condition::Greater(c) => (">", c),
+                        Condition::GreaterOrEqual(c) => ("\\geq", c),
+                    };
+                    branch.write_latex(out);
+                    write!(out, " & x {} ", operator).unwrap();
+                    write_number(out, bound);
+                }
+                out.push_str(" \\end{cases}");
+            }
+        }
+    }
+
+    // Like `fmt_operand`, except that a fraction is already visually grouped and only
+    // needs parentheses as the base of a power.
+    fn write_latex_operand(&self, out: &mut String, min_precedence: u8) {
+        let precedence = match self {
+            Expression::Quotient(..) if min_precedence < ATOM => ATOM,
+            other => other.precedence(),
+        };
+        if precedence < min_precedence {
+            self.write_latex_parenthesized(out);
+        } else {
+            self.write_latex(out);
+        }
+    }
+
+    fn write_latex_parenthesized(&self, out: &mut String) {
+        out.push_str("\\left(");
+        self.write_latex(out);
+        out.push_str("\\right)");
+    }
+}
+
+fn write_function(out: &mut String, name: &str, argument: &Expression) {
+    out.push_str(name);
+    argument.write_latex_parenthesized(out);
+}
+
+fn write_number(out: &mut String, value: f64) {
+    if value == f64::INFINITY {
+        out.push_str("\\infty");
+    } else if value == f64::NEG_INFINITY {
+        out.push_str("-\\infty");
+    } else {
+        write!(out, "{}", value).unwrap();
+    }
+}
+
+fn limit_point_latex(at: LimitPoint) -> String {
+    let mut out = String::new();
+    match at {
+        LimitPoint::Finite(a) => write_number(&mut out, a),
+        LimitPoint::Left(a) => {
+            write_number(&mut out, a);
+            out.push_str("^{-}");
+        }
+        LimitPoint::Right(a) => {
+            write_number(&mut out, a);
+            out.push_str("^{+}");
+        }
+        LimitPoint::PosInfinity => out.push_str("\\infty"),
+        LimitPoint::NegInfinity => out.push_str("-\\infty"),
+    }
+    out
+}
+
+impl LhopitalResult {
+    /// Renders the derivation as a LaTeX `aligned` block, chaining every quotient the
+    /// solver evaluated in a limit taken at `at` and ending with the value.
+    pub fn pretty_steps(&self, at: impl Into<LimitPoint>) -> String {
+        let limit = format!("\\lim_{{x \\to {}}}", limit_point_latex(at.into()));
+        let mut rows: Vec<String> = self
+            .iterations
+            .iter()
+            .map(|step| {
+                let quotient = Expression::Quotient(
+                    Rc::new(step.numerator.clone()),
+                    Rc::new(step.denominator.clone()),
+                );
+                format!("{} {}", limit, quotient.to_latex())
+            })
+            .collect();
+        let mut value = String::new();
+        write_number(&mut value, self.value);
+        rows.push(value);
+
+        let mut out = String::from("\\begin{aligned}\n");
+        out.push_str(&rows[0]);
+        for (i, row) in rows[1..].iter().enumerate() {
+            if i > 0 {
+                out.push_str(" \\\\\n");
+            } else {
+                out.push(' ');
+            }
+            out.push_str("&= ");
+            out.push_str(row);
+        }
+        out.push_str("\n\\end{aligned}");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lhopital_solve_traced;
+
+    use super::*;
+
+    fn parse(input: &str) -> Expression {
+        Expression::parse(input).unwrap()
+    }
+
+    #[test]
+    fn pretty_steps() {
+        let result = lhopital_solve_traced(&parse("x^2 - 4"), &parse("x - 2"), 2.0, 20).unwrap();
+        assert_eq!(
+            result.pretty_steps(2.0),
+            "\\begin{aligned}\n\
+             \\lim_{x \\to 2} \\frac{x^{2} - 4}{x - 2} &= \\lim_{x \\to 2} \\frac{x + 2}{1} \\\\\n\
+             &= 4\n\
+             \\end{aligned}"
+        );
+        assert_eq!(
+            parse("(x^2 - 4) / (x - 2)").to_latex(),
+            "\\frac{x^{2} - 4}{x - 2}"
+        );
+    }
+}
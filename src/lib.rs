@@ -0,0 +1,52 @@
+//! L'Hôpital's Rule CAS solver: symbolic expressions, differentiation and limit
+//! evaluation.
+
+mod builder;
+mod cache;
+#[cfg(feature = "complex")]
+mod complex;
+mod config;
+mod differentiable;
+mod display;
+mod error;
+mod expression;
+#[cfg(feature = "highprec")]
+mod highprec;
+mod integrate;
+#[cfg(feature = "json")]
+mod json;
+mod latex;
+mod parser;
+#[cfg(any(test, feature = "testing"))]
+mod random;
+#[cfg(feature = "rational")]
+mod rational;
+mod rule;
+mod sexpr;
+mod simplify;
+mod solver;
+mod visitor;
+
+#[cfg(feature = "highprec")]
+pub use bigdecimal::BigDecimal;
+pub use cache::DerivativeCache;
+pub use config::SolveConfig;
+pub use differentiable::Differentiable;
+pub use error::{LhopitalError, ParseError};
+pub use expression::{Condition, DEFAULT_MAX_DEPTH, Expression};
+#[cfg(feature = "json")]
+pub use json::solve_json;
+#[cfg(feature = "complex")]
+pub use num_complex::Complex64;
+pub use rule::{Rule, RuleTree};
+pub use solver::{
+    DEFAULT_MAX_ITERATIONS, DEFAULT_MAX_MAGNITUDE, DEFAULT_TOLERANCE, DetectedForm, Direction,
+    FormReport, INFINITY_PROXY, LhopitalResult, LhopitalStep, LimitPoint, NUMERIC_EPSILON,
+    ProbedForm, QuotientForm, Step, Strategy, apply_lhopital_once, assert_indeterminate,
+    classify_form, combine_fractions, lhopital_applicable, lhopital_order, lhopital_solve,
+    lhopital_solve_default, lhopital_solve_power, lhopital_solve_quotient, lhopital_solve_traced,
+    lhopital_solve_with, lhopital_solve_with_tolerance, limit, limit_table, numeric_limit, probe,
+    recognize_constant, sample_around, solve_all_forms, solve_batch, solve_with_trace_to_writer,
+    to_quotient_form, try_factor_cancel,
+};
+pub use visitor::{NodeCounter, Visitor};
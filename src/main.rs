@@ -1,117 +1,119 @@
-#[derive(Debug, Clone)]
-enum Expression {
-    Constant(f64),
-    Variable, 
-    Sum(Box<Expression>, Box<Expression>),
-    Difference(Box<Expression>, Box<Expression>),
-    Product(Box<Expression>, Box<Expression>),
-    Power(Box<Expression>, f64),
-}
-
-impl Expression {
-    fn evaluate(&self, x: f64) -> f64 {
-        match self {
-            Expression::Constant(c) => *c,
-            Expression::Variable => x,
-            Expression::Sum(a, b) => a.evaluate(x) + b.evaluate(x),
-            Expression::Difference(a, b) => a.evaluate(x) - b.evaluate(x),
-            Expression::Product(a, b) => a.evaluate(x) * b.evaluate(x),
-            Expression::Power(base, exp) => base.evaluate(x).powf(*exp),
-        }
-    }
+use std::env;
+use std::process::ExitCode;
 
-    fn differentiate(&self) -> Self {
-        match self {
-            // d/dx(c) = 0
-            Expression::Constant(_) => Expression::Constant(0.0),
-            // d/dx(x) = 1
-            Expression::Variable => Expression::Constant(1.0),
-            // d/dx(f+g) = f' + g'
-            Expression::Sum(a, b) => Expression::Sum(
-                Box::new(a.differentiate()),
-                Box::new(b.differentiate()),
-            ),
-            // d/dx(f-g) = f' - g'
-            Expression::Difference(a, b) => Expression::Difference(
-                Box::new(a.differentiate()),
-                Box::new(b.differentiate()),
-            ),
-            // d/dx(x^n) = n*x^(n-1) (Power Rule simplified for this example)
-            Expression::Power(base, exp) => {
-                if let Expression::Variable = **base {
-                    Expression::Product(
-                        Box::new(Expression::Constant(*exp)),
-                        Box::new(Expression::Power(base.clone(), exp - 1.0)),
-                    )
-                } else {
-                    panic!("Differentiation for this power expression is not implemented.");
-                }
-            }
-            _ => panic!("Differentiation rule not implemented for this expression."),
-        }
-    }
-}
+use lhopital::{
+    DEFAULT_TOLERANCE, Expression, LimitPoint, lhopital_solve_traced, recognize_constant,
+};
 
-fn lhopital_solve(
-    numerator: &Expression,
-    denominator: &Expression,
-    at: f64,
-    max_iterations: u32,
-) -> Result<f64, String> {
-    let mut num = numerator.clone();
-    let mut den = denominator.clone();
+const USAGE: &str = "Usage: lhopital --num <expr> --den <expr> --at <point> [--max-iter <n>]
+                [--precision <n>]
 
-    for i in 0..max_iterations {
-        println!("Iteration {}:", i);
-        println!("  Numerator: {:?}", num);
-        println!("  Denominator: {:?}", den);
+Finds the limit of num / den as x approaches the point. The point may be a number,
+`inf` or `-inf`, and a trailing `-` or `+` makes a finite point one-sided.
+Values are shown with 4 decimal places unless --precision says otherwise.
+Without any arguments, the limit of (x^2 - 4) / (x - 2) as x -> 2 is shown.";
 
-        let num_val = num.evaluate(at);
-        let den_val = den.evaluate(at);
+struct Args {
+    numerator: Expression,
+    denominator: Expression,
+    at: LimitPoint,
+    max_iterations: u32,
+    precision: usize,
+}
 
-        println!("  Evaluated at x = {}: {:.4} / {:.4}", at, num_val, den_val);
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        numerator: Expression::var().pow(2.0) - 4.0,
+        denominator: Expression::var() - 2.0,
+        at: LimitPoint::Finite(2.0),
+        max_iterations: 5,
+        precision: 4,
+    };
+    let (mut numerator, mut denominator, mut at) = (None, None, None);
 
-        if num_val.abs() < 1e-9 && den_val.abs() < 1e-9 {
-             println!("  Result is 0/0. Applying L'Hôpital's Rule.");
-            num = num.differentiate();
-            den = den.differentiate();
-        } else if den_val.abs() < 1e-9 {
-            return Err(String::from("Limit results in division by zero."));
-        } else {
-            println!("  Limit found.");
-            return Ok(num_val / den_val);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for '{}'.", flag))?;
+        match flag.as_str() {
+            "--num" => numerator = Some(Expression::parse(&value).map_err(|e| e.to_string())?),
+            "--den" => denominator = Some(Expression::parse(&value).map_err(|e| e.to_string())?),
+            "--at" => at = Some(value.parse::<LimitPoint>().map_err(|e| e.to_string())?),
+            "--max-iter" => {
+                parsed.max_iterations = value
+                    .parse()
+                    .map_err(|_| format!("Invalid iteration count '{}'.", value))?;
+            }
+            "--precision" => {
+                parsed.precision = value
+                    .parse()
+                    .map_err(|_| format!("Invalid precision '{}'.", value))?;
+            }
+            _ => return Err(format!("Unknown argument '{}'.", flag)),
         }
     }
 
-    Err(String::from(
-        "Exceeded max iterations, could not find a determinate form.",
-    ))
+    // Any of the three given means the built-in example is not wanted, so all are needed.
+    if numerator.is_some() || denominator.is_some() || at.is_some() {
+        parsed.numerator = numerator.ok_or("Missing --num.")?;
+        parsed.denominator = denominator.ok_or("Missing --den.")?;
+        parsed.at = at.ok_or("Missing --at.")?;
+    }
+    Ok(parsed)
 }
 
-fn main() {
-    // We want to find the limit as x -> 2 of (x^2 - 4) / (x - 2)
-
-    // Numerator: x^2 - 4
-    let numerator = Expression::Difference(
-        Box::new(Expression::Power(Box::new(Expression::Variable), 2.0)),
-        Box::new(Expression::Constant(4.0)),
-    );
-
-    // Denominator: x - 2
-    let denominator = Expression::Difference(
-        Box::new(Expression::Variable),
-        Box::new(Expression::Constant(2.0)),
-    );
-
-    let limit_point = 2.0;
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let Args {
+        numerator,
+        denominator,
+        at,
+        max_iterations,
+        precision,
+    } = match parse_args(args.into_iter()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
 
     println!(
-        "Calculating limit of f(x) = (x^2 - 4) / (x - 2) as x -> {}\n",
-        limit_point
+        "Calculating limit of f(x) = ({}) / ({}) as x -> {}\n",
+        numerator, denominator, at
     );
 
-    match lhopital_solve(&numerator, &denominator, limit_point, 5) {
-        Ok(result) => println!("\nFinal Result: {}", result),
-        Err(e) => eprintln!("\nError: {}", e),
+    match lhopital_solve_traced(&numerator, &denominator, at, max_iterations) {
+        Ok(result) => {
+            for (i, step) in result.iterations.iter().enumerate() {
+                println!("Iteration {}:", i);
+                println!("  Numerator: {}", step.numerator);
+                println!("  Denominator: {}", step.denominator);
+                println!(
+                    "  Evaluated at x = {}: {:.*} / {:.*}",
+                    at.sample_point(),
+                    precision,
+                    step.numerator_value,
+                    precision,
+                    step.denominator_value
+                );
+                if let Some(form) = step.form {
+                    println!("  Form: {}", form);
+                }
+            }
+            match recognize_constant(result.value, DEFAULT_TOLERANCE) {
+                Some(name) => println!("\nFinal Result: {:.*} ≈ {}", precision, result.value, name),
+                None => println!("\nFinal Result: {:.*}", precision, result.value),
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("\nError: {}", e);
+            ExitCode::FAILURE
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,313 @@
+use std::rc::Rc;
+
+use crate::{Expression, ParseError};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => n.to_string(),
+            Token::Ident(name) => name.clone(),
+            Token::Plus => String::from("+"),
+            Token::Minus => String::from("-"),
+            Token::Star => String::from("*"),
+            Token::Slash => String::from("/"),
+            Token::Caret => String::from("^"),
+            Token::LParen => String::from("("),
+            Token::RParen => String::from(")"),
+            Token::Comma => String::from(","),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(pos, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => {
+                let mut literal = String::new();
+                while let Some(&(_, d)) = chars.peek() {
+                    if d.is_ascii_digit() || d == '.' {
+                        literal.push(d);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = literal
+                    .parse()
+                    .map_err(|_| ParseError::InvalidNumber(literal.clone()))?;
+                tokens.push((Token::Number(value), pos));
+                continue;
+            }
+            c if c.is_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&(_, d)) = chars.peek() {
+                    if d.is_alphanumeric() {
+                        name.push(d);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Ident(name), pos));
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            other => return Err(ParseError::UnexpectedCharacter(other, pos)),
+        };
+        chars.next();
+        tokens.push((token, pos));
+    }
+
+    Ok(tokens)
+}
+
+// Recursive-descent parser over the token stream. Precedence from loosest to
+// tightest: `+ -`, `* /`, unary minus, `^` (right-associative), atoms.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.next() {
+            Some((token, _)) if token == expected => Ok(()),
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, ParseError> {
+        let mut lhs = self.parse_product()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.next();
+                    let rhs = self.parse_product()?;
+                    lhs = Expression::Sum(Rc::new(lhs), Rc::new(rhs));
+                }
+                Some(Token::Minus) => {
+                    self.next();
+                    let rhs = self.parse_product()?;
+                    lhs = Expression::Difference(Rc::new(lhs), Rc::new(rhs));
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expression, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.next();
+                    let rhs = self.parse_unary()?;
+                    lhs = Expression::Product(Rc::new(lhs), Rc::new(rhs));
+                }
+                Some(Token::Slash) => {
+                    self.next();
+                    let rhs = self.parse_unary()?;
+                    lhs = Expression::Quotient(Rc::new(lhs), Rc::new(rhs));
+                }
+                _ => return Ok(lhs),
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.next();
+                let operand = self.parse_unary()?;
+                Ok(match operand {
+                    Expression::Constant(c) => Expression::Constant(-c),
+                    other => Expression::Negate(Rc::new(other)),
+                })
+            }
+            Some(Token::Plus) => {
+                self.next();
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Expression, ParseError> {
+        let base = self.parse_atom()?;
+        if self.peek() != Some(&Token::Caret) {
+            return Ok(base);
+        }
+        self.next();
+        let exponent = self.parse_unary()?;
+        if exponent.contains_variable() {
+            return Ok(Expression::PowerExpr(Rc::new(base), Rc::new(exponent)));
+        }
+        Ok(Expression::Power(Rc::new(base), exponent.evaluate(0.0)))
+    }
+
+    fn parse_atom(&mut self) -> Result<Expression, ParseError> {
+        match self.next() {
+            Some((Token::Number(n), _)) => Ok(Expression::Constant(n)),
+            Some((Token::Ident(name), _)) if name == "x" => Ok(Expression::Variable),
+            Some((Token::Ident(name), _)) if name == "pi" => Ok(Expression::Pi),
+            Some((Token::Ident(name), _)) if name == "e" => Ok(Expression::E),
+            Some((Token::Ident(name), _)) if name.chars().count() == 1 => {
+                Ok(Expression::Symbol(name.chars().next().unwrap()))
+            }
+            Some((Token::Ident(name), _)) if name == "log" => {
+                // log(b, f) with a constant base b
+                self.expect(Token::LParen)?;
+                let base = self.parse_sum()?;
+                if base.contains_variable() {
+                    return Err(ParseError::NonConstantBase);
+                }
+                self.expect(Token::Comma)?;
+                let argument = self.parse_sum()?;
+                self.expect(Token::RParen)?;
+                Ok(Expression::Log(base.evaluate(0.0), Rc::new(argument)))
+            }
+            Some((Token::Ident(name), _)) if name == "root" => {
+                // root(f, n) with a constant degree n
+                self.expect(Token::LParen)?;
+                let argument = self.parse_sum()?;
+                self.expect(Token::Comma)?;
+                let degree = self.parse_sum()?;
+                if degree.contains_variable() {
+                    return Err(ParseError::NonConstantExponent);
+                }
+                self.expect(Token::RParen)?;
+                Ok(Expression::Root(Rc::new(argument), degree.evaluate(0.0)))
+            }
+            Some((Token::Ident(name), _)) => {
+                let function: fn(Rc<Expression>) -> Expression = match name.as_str() {
+                    "sqrt" => |a| Expression::Root(a, 2.0),
+                    "sin" => Expression::Sin,
+                    "cos" => Expression::Cos,
+                    "tan" => Expression::Tan,
+                    "recip" => Expression::Reciprocal,
+                    "sec" => Expression::Sec,
+                    "csc" => Expression::Csc,
+                    "cot" => Expression::Cot,
+                    "atan" => Expression::Atan,
+                    "sinh" => Expression::Sinh,
+                    "cosh" => Expression::Cosh,
+                    "abs" => Expression::Abs,
+                    "sign" => Expression::Sign,
+                    "exp" => Expression::Exp,
+                    "ln" => Expression::Ln,
+                    "log2" => |a| Expression::Log(2.0, a),
+                    "log10" => |a| Expression::Log(10.0, a),
+                    _ => return Err(ParseError::UnknownIdentifier(name)),
+                };
+                self.expect(Token::LParen)?;
+                let argument = self.parse_sum()?;
+                self.expect(Token::RParen)?;
+                Ok(function(Rc::new(argument)))
+            }
+            Some((Token::LParen, _)) => {
+                let inner = self.parse_sum()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+impl Expression {
+    /// Parses an expression in `x` such as `"(x^2 - 4)/(x - 2)"`. `pi` and `e` are the
+    /// named constants [`Expression::Pi`] and [`Expression::E`], and any other single
+    /// letter becomes an [`Expression::Symbol`].
+    ///
+    /// Supports numeric constants, `+ - * / ^`, parentheses and the functions `sqrt`,
+    /// `root(f, n)`, `sin`, `cos`, `tan`, `sec`, `csc`, `cot`, `recip`, `atan`, `sinh`,
+    /// `cosh`, `abs`, `sign`, `exp`, `ln`, `log2`, `log10` and `log(base, f)`, with the
+    /// usual precedence. An exponent containing a variable gives an
+    /// [`Expression::PowerExpr`]; root degrees and logarithm bases must be constant.
+    pub fn parse(input: &str) -> Result<Expression, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let expr = parser.parse_sum()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(input: &str, expected: fn(f64) -> f64) {
+        let expr = Expression::parse(input).unwrap();
+        for x in [0.5, 3.0, 7.25] {
+            let value = expr.evaluate(x);
+            assert!((value - expected(x)).abs() < 1e-12, "{} at {}", input, x);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_evaluate() {
+        assert_round_trips("(x^2 - 4) / (x - 2)", |x| (x * x - 4.0) / (x - 2.0));
+        assert_round_trips("-x^2 + 2*x - 3/4", |x| -(x * x) + 2.0 * x - 0.75);
+        assert_round_trips("2^3^2 + x", |x| 512.0 + x);
+        assert_round_trips("x^-1", |x| 1.0 / x);
+        assert_round_trips("sin(x) + ln(exp(x)) * sqrt(x)", |x| x.sin() + x * x.sqrt());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(Expression::parse("(x"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            Expression::parse("x $"),
+            Err(ParseError::UnexpectedCharacter('$', 2))
+        );
+        assert_eq!(
+            Expression::parse("foo(x)"),
+            Err(ParseError::UnknownIdentifier(String::from("foo")))
+        );
+    }
+}
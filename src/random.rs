@@ -0,0 +1,142 @@
+use std::rc::Rc;
+
+use rand::Rng;
+
+use crate::{Condition, Expression};
+
+impl Expression {
+    /// A random expression in `x`, nested at most `max_depth` levels deep.
+    ///
+    /// This is a generator for property tests and fuzzing, and outside the crate's own
+    /// tests is only available with the `testing` feature. It is not meant for anything
+    /// else, and the trees it produces for a given `rng` may change between releases.
+    ///
+    /// Every variant can appear except [`Expression::Symbol`] and
+    /// [`Expression::Derivative`], so the result depends on `x` alone and evaluating it
+    /// never differentiates. Constants are small whole numbers, exponents run from -2 to 3,
+    /// roots are square or cube roots, and a piecewise expression splits at one bound.
+    /// Nothing keeps the expression inside its domain, so it may well evaluate to NaN,
+    /// and a node with a pole or rapid growth, like a quotient, a negative power, `exp`
+    /// or `sec`, can make it evaluate to an infinity. Without any such node the value is
+    /// always finite or NaN.
+    pub fn random(max_depth: usize, rng: &mut impl Rng) -> Expression {
+        if max_depth <= 1 || rng.random_bool(0.25) {
+            return random_leaf(rng);
+        }
+        let depth = max_depth - 1;
+        match rng.random_range(0..24) {
+            0 => Expression::Sum(random_operand(depth, rng), random_operand(depth, rng)),
+            1 => Expression::Difference(random_operand(depth, rng), random_operand(depth, rng)),
+            2 => Expression::Negate(random_operand(depth, rng)),
+            3 => Expression::Product(random_operand(depth, rng), random_operand(depth, rng)),
+            4 => Expression::Quotient(random_operand(depth, rng), random_operand(depth, rng)),
+            5 => Expression::Reciprocal(random_operand(depth, rng)),
+            6 => Expression::Power(random_operand(depth, rng), rng.random_range(-2..=3) as f64),
+            7 => Expression::PowerExpr(random_operand(depth, rng), random_operand(depth, rng)),
+            8 => Expression::Root(random_operand(depth, rng), rng.random_range(2..=3) as f64),
+            9 => Expression::Sin(random_operand(depth, rng)),
+            10 => Expression::Cos(random_operand(depth, rng)),
+            11 => Expression::Tan(random_operand(depth, rng)),
+            12 => Expression::Sec(random_operand(depth, rng)),
+            13 => Expression::Csc(random_operand(depth, rng)),
+            14 => Expression::Cot(random_operand(depth, rng)),
+            15 => Expression::Atan(random_operand(depth, rng)),
+            16 => Expression::Sinh(random_operand(depth, rng)),
+            17 => Expression::Cosh(random_operand(depth, rng)),
+            18 => Expression::Abs(random_operand(depth, rng)),
+            19 => Expression::Sign(random_operand(depth, rng)),
+            20 => Expression::Exp(random_operand(depth, rng)),
+            21 => Expression::Ln(random_operand(depth, rng)),
+            22 => Expression::Log(rng.random_range(2..=10) as f64, random_operand(depth, rng)),
+            _ => {
+                let bound = rng.random_range(-3..=3) as f64;
+                Expression::Piecewise(vec![
+                    (Condition::Less(bound), random_operand(depth, rng)),
+                    (Condition::GreaterOrEqual(bound), random_operand(depth, rng)),
+                ])
+            }
+        }
+    }
+}
+
+fn random_operand(max_depth: usize, rng: &mut impl Rng) -> Rc<Expression> {
+    Rc::new(Expression::random(max_depth, rng))
+}
+
+fn random_leaf(rng: &mut impl Rng) -> Expression {
+    match rng.random_range(0..5) {
+        0 => Expression::Pi,
+        1 => Expression::E,
+        2 => Expression::Constant(rng.random_range(-9..=9) as f64),
+        #[cfg(feature = "rational")]
+        3 => Expression::rational(rng.random_range(-9..=9), rng.random_range(2..=9)),
+        _ => Expression::Variable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn respects_max_depth() {
+        let mut rng = StdRng::seed_from_u64(105);
+        for max_depth in 1..6 {
+            for _ in 0..200 {
+                let expr = Expression::random(max_depth, &mut rng);
+                assert!(expr.depth() <= max_depth, "{}", expr);
+                assert!(
+                    !expr.iter_nodes().any(|node| matches!(
+                        node,
+                        Expression::Symbol(_) | Expression::Derivative(_)
+                    ))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_tree() {
+        let tree = |seed| Expression::random(5, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(tree(7), tree(7));
+    }
+
+    #[test]
+    fn evaluates_to_finite_or_nan() {
+        // Nodes that can evaluate to an infinity from finite operands.
+        let can_blow_up = |node: &Expression| {
+            matches!(
+                node,
+                Expression::Quotient(..)
+                    | Expression::Reciprocal(_)
+                    | Expression::PowerExpr(..)
+                    | Expression::Tan(_)
+                    | Expression::Sec(_)
+                    | Expression::Csc(_)
+                    | Expression::Cot(_)
+                    | Expression::Sinh(_)
+                    | Expression::Cosh(_)
+                    | Expression::Exp(_)
+                    | Expression::Ln(_)
+                    | Expression::Log(..)
+            ) || matches!(node, Expression::Power(_, n) if *n < 0.0)
+        };
+        let mut rng = StdRng::seed_from_u64(105);
+        for _ in 0..20_000 {
+            let expr = Expression::random(5, &mut rng);
+            for x in [-2.5, 0.0, 0.7, 3.0] {
+                let value = expr.evaluate(x);
+                assert!(
+                    value.is_finite() || value.is_nan() || expr.iter_nodes().any(can_blow_up),
+                    "{} at {} is {}",
+                    expr,
+                    x,
+                    value
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::Expression;
+
+// Integers up to this magnitude are exact as f64, so constants within it can take part
+// in exact arithmetic.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+impl Expression {
+    /// The exact fraction `numerator / denominator`, reduced to lowest terms with a
+    /// positive denominator.
+    ///
+    /// Whole numbers are exact as floats already, so a fraction that reduces to one is
+    /// returned as an [`Expression::Constant`]. Either part overflowing after
+    /// reduction gives the nearest `Constant` instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `denominator` is zero.
+    pub fn rational(numerator: i64, denominator: i64) -> Expression {
+        assert!(denominator != 0, "rational with a zero denominator");
+        reduce(numerator.into(), denominator.into())
+    }
+}
+
+fn reduce(numerator: i128, denominator: i128) -> Expression {
+    let divisor = gcd(numerator, denominator) * denominator.signum();
+    let (n, d) = (numerator / divisor, denominator / divisor);
+    match (i64::try_from(n), i64::try_from(d)) {
+        (Ok(n), Ok(1)) => Expression::Constant(n as f64),
+        (Ok(n), Ok(d)) => Expression::Rational(n, d),
+        _ => Expression::Constant(n as f64 / d as f64),
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+// The value of a numeric leaf.
+fn value(expr: &Expression) -> Option<f64> {
+    match *expr {
+        Expression::Rational(n, d) => Some(n as f64 / d as f64),
+        Expression::Constant(c) => Some(c),
+        _ => None,
+    }
+}
+
+// The exact value of a rational or of a whole-number constant.
+fn fraction(expr: &Expression) -> Option<(i128, i128)> {
+    match *expr {
+        Expression::Rational(n, d) => Some((n.into(), d.into())),
+        Expression::Constant(c) if c.fract() == 0.0 && c.abs() <= MAX_EXACT_INTEGER => {
+            Some((c as i128, 1))
+        }
+        _ => None,
+    }
+}
+
+// Folds `a op b` when at least one operand is a rational and the other is a number:
+// exactly if both have exact values, and in floating point otherwise. Division by
+// zero is not folded, so it stays visible in the tree.
+pub(crate) fn combine(a: &Expression, b: &Expression, op: Op) -> Option<Expression> {
+    if !matches!(a, Expression::Rational(..)) && !matches!(b, Expression::Rational(..)) {
+        return None;
+    }
+    let Some(((an, ad), (bn, bd))) = fraction(a).zip(fraction(b)) else {
+        let (a, b) = (value(a)?, value(b)?);
+        return match op {
+            Op::Add => Some(Expression::Constant(a + b)),
+            Op::Sub => Some(Expression::Constant(a - b)),
+            Op::Mul => Some(Expression::Constant(a * b)),
+            Op::Div => (b != 0.0).then(|| Expression::Constant(a / b)),
+        };
+    };
+    let (n, d) = match op {
+        Op::Add => (an * bd + bn * ad, ad * bd),
+        Op::Sub => (an * bd - bn * ad, ad * bd),
+        Op::Mul => (an * bn, ad * bd),
+        Op::Div => (an * bd, ad * bn),
+    };
+    (d != 0).then(|| reduce(n, d))
+}
+
+// Folds a sum, difference, product or quotient of numbers with `combine`.
+pub(crate) fn fold(expr: &Expression) -> Option<Expression> {
+    let (a, b, op) = match expr {
+        Expression::Sum(a, b) => (a, b, Op::Add),
+        Expression::Difference(a, b) => (a, b, Op::Sub),
+        Expression::Product(a, b) => (a, b, Op::Mul),
+        Expression::Quotient(a, b) => (a, b, Op::Div),
+        _ => return None,
+    };
+    combine(a, b, op)
+}
+
+pub(crate) fn negate(n: i64, d: i64) -> Expression {
+    reduce(-i128::from(n), d.into())
+}
+
+// The exact reciprocal of a whole number, such as the `1/n` factor in the derivative
+// of an `n`th root.
+pub(crate) fn reciprocal(n: f64) -> Option<Expression> {
+    let (n, _) = fraction(&Expression::Constant(n))?;
+    (n != 0).then(|| reduce(1, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn coefficients_stay_exact() {
+        let cube = Expression::parse("x^3").unwrap();
+        let first = cube.differentiate().unwrap();
+        let second = first.differentiate().unwrap();
+        let term = |coefficient: f64, power: Expression| {
+            Expression::Product(Rc::new(Expression::Constant(coefficient)), Rc::new(power))
+        };
+        assert_eq!(first, term(3.0, Expression::var().pow(2.0)));
+        assert_eq!(second, term(6.0, Expression::var()));
+    }
+
+    #[test]
+    fn roots_have_exact_coefficients() {
+        let derivative = Expression::parse("root(x, 3)")
+            .unwrap()
+            .differentiate()
+            .unwrap();
+        let Expression::Product(coefficient, _) = &derivative else {
+            panic!("{:?}", derivative);
+        };
+        assert_eq!(**coefficient, Expression::Rational(1, 3));
+        assert_eq!(Expression::rational(6, -4), Expression::Rational(-3, 2));
+        assert_eq!(Expression::rational(6, 3), Expression::Constant(2.0));
+    }
+}
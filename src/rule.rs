@@ -0,0 +1,173 @@
+use std::fmt;
+
+use crate::{Expression, LhopitalError};
+
+/// A differentiation rule, as applied at one node by [`Expression::differentiate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// d/dx(c) = 0, also covering other variables and `f^0`.
+    Constant,
+    /// d/dx(x) = 1.
+    Identity,
+    Sum,
+    Difference,
+    Negation,
+    Product,
+    Quotient,
+    Reciprocal,
+    /// d/dx(f^n) = n*f^(n-1) * f', also covering roots.
+    Power,
+    /// d/dx(f^g) for an exponent that is itself an expression.
+    GeneralPower,
+    Trigonometric,
+    InverseTrigonometric,
+    Hyperbolic,
+    AbsoluteValue,
+    Exponential,
+    Logarithm,
+    Piecewise,
+    /// Differentiating an [`Expression::Derivative`] again.
+    HigherDerivative,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rule::Constant => "constant rule",
+            Rule::Identity => "identity rule",
+            Rule::Sum => "sum rule",
+            Rule::Difference => "difference rule",
+            Rule::Negation => "negation rule",
+            Rule::Product => "product rule",
+            Rule::Quotient => "quotient rule",
+            Rule::Reciprocal => "reciprocal rule",
+            Rule::Power => "power rule",
+            Rule::GeneralPower => "general power rule",
+            Rule::Trigonometric => "trigonometric rule",
+            Rule::InverseTrigonometric => "inverse trigonometric rule",
+            Rule::Hyperbolic => "hyperbolic rule",
+            Rule::AbsoluteValue => "absolute value rule",
+            Rule::Exponential => "exponential rule",
+            Rule::Logarithm => "logarithm rule",
+            Rule::Piecewise => "piecewise rule",
+            Rule::HigherDerivative => "higher derivative rule",
+        })
+    }
+}
+
+/// The rules applied while differentiating an expression, shaped like the expression:
+/// one node per subexpression that was differentiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleTree {
+    /// The rule applied at this node.
+    pub rule: Rule,
+    /// Whether the chain rule follows `rule`, which it does for a function or power
+    /// of something other than `x` itself.
+    pub chain: bool,
+    /// The trees of the operands differentiated along the way, in order. Leaves and
+    /// `x^n`, which is differentiated in one step, have none.
+    pub children: Vec<RuleTree>,
+}
+
+impl RuleTree {
+    // Appends the rules from this node down, with `"chain rule"` after any rule it
+    // follows, as listed by `Expression::differentiate_explained`.
+    pub(crate) fn flatten(&self, rules: &mut Vec<String>) {
+        rules.push(self.rule.to_string());
+        if self.chain {
+            rules.push(String::from("chain rule"));
+        }
+        for child in &self.children {
+            child.flatten(rules);
+        }
+    }
+}
+
+impl Expression {
+    /// Like [`Expression::differentiate`], but also returns the [`RuleTree`] of the
+    /// rules applied, so `sin(x) * x` has [`Rule::Product`] at its root with the
+    /// trigonometric and identity rules below it.
+    pub fn differentiate_annotated(&self) -> Result<(Self, RuleTree), LhopitalError> {
+        let derivative = self.differentiate()?;
+        Ok((derivative, self.rule_tree()))
+    }
+
+    // The rules `derive` applies to this subtree, mirroring its recursion. Only called
+    // once `differentiate` has succeeded, so every node has a rule.
+    pub(crate) fn rule_tree(&self) -> RuleTree {
+        let leaf = |rule| RuleTree {
+            rule,
+            chain: false,
+            children: Vec::new(),
+        };
+        let (rule, chained) = match self {
+            Expression::Constant(_) | Expression::Pi | Expression::E | Expression::Symbol(_) => {
+                (Rule::Constant, None)
+            }
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => (Rule::Constant, None),
+            Expression::Variable => (Rule::Identity, None),
+            Expression::Sum(..) => (Rule::Sum, None),
+            Expression::Difference(..) => (Rule::Difference, None),
+            Expression::Negate(_) => (Rule::Negation, None),
+            Expression::Product(..) => (Rule::Product, None),
+            Expression::Quotient(..) => (Rule::Quotient, None),
+            Expression::Reciprocal(_) => (Rule::Reciprocal, None),
+            Expression::Power(_, exp) if *exp == 0.0 => return leaf(Rule::Constant),
+            // `x^n` is differentiated in one step, without differentiating `x`.
+            Expression::Power(base, _) if matches!(**base, Expression::Variable) => {
+                return leaf(Rule::Power);
+            }
+            Expression::Power(base, _) => (Rule::Power, Some(base)),
+            Expression::PowerExpr(..) => (Rule::GeneralPower, None),
+            Expression::Root(a, _) => (Rule::Power, Some(a)),
+            Expression::Sin(a)
+            | Expression::Cos(a)
+            | Expression::Tan(a)
+            | Expression::Sec(a)
+            | Expression::Csc(a)
+            | Expression::Cot(a) => (Rule::Trigonometric, Some(a)),
+            Expression::Atan(a) => (Rule::InverseTrigonometric, Some(a)),
+            Expression::Sinh(a) | Expression::Cosh(a) => (Rule::Hyperbolic, Some(a)),
+            Expression::Abs(a) => (Rule::AbsoluteValue, Some(a)),
+            Expression::Exp(a) => (Rule::Exponential, Some(a)),
+            Expression::Ln(a) | Expression::Log(_, a) => (Rule::Logarithm, Some(a)),
+            Expression::Piecewise(_) => (Rule::Piecewise, None),
+            Expression::Derivative(_) => (Rule::HigherDerivative, None),
+            // `differentiate` has already rejected this.
+            Expression::Sign(_) => unreachable!("sign has no differentiation rule"),
+        };
+        RuleTree {
+            rule,
+            chain: chained.is_some_and(|inner| !matches!(**inner, Expression::Variable)),
+            children: self.children().map(Expression::rule_tree).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn product_at_the_root() {
+        let expr = Expression::parse("sin(x) * x").unwrap();
+        let (derivative, tree) = expr.differentiate_annotated().unwrap();
+        assert_eq!(derivative, expr.differentiate().unwrap());
+        assert_eq!(tree.rule, Rule::Product);
+        assert!(!tree.chain);
+        let rules: Vec<_> = tree.children.iter().map(|child| child.rule).collect();
+        assert_eq!(rules, [Rule::Trigonometric, Rule::Identity]);
+    }
+
+    #[test]
+    fn chain_rule_follows_an_inner_function() {
+        let (_, tree) = Expression::parse("sin(x^2)")
+            .unwrap()
+            .differentiate_annotated()
+            .unwrap();
+        assert_eq!(tree.rule, Rule::Trigonometric);
+        assert!(tree.chain);
+        assert_eq!(tree.children[0].rule, Rule::Power);
+    }
+}
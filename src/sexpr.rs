@@ -0,0 +1,419 @@
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::{Condition, Expression, ParseError};
+
+impl Expression {
+    /// Renders the expression as a Lisp-style s-expression, e.g. `(- (^ x 2) 4)` for
+    /// `x^2 - 4`.
+    ///
+    /// Every node is written in prefix form with its operands in order, so unlike the
+    /// infix rendering it needs no precedence rules and
+    /// [`Expression::from_sexpr`] reads it back as exactly the same tree.
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        self.write_sexpr(&mut out);
+        out
+    }
+
+    /// Parses an s-expression written by [`Expression::to_sexpr`].
+    ///
+    /// Atoms are numbers, `x`, `pi`, `e` and single-letter symbols. Lists are `(+ a b)`,
+    /// `(- a b)`, `(- a)`, `(* a b)`, `(/ a b)`, `(^ a n)` with a numeric exponent,
+    /// `(pow a b)` with an expression exponent, `(root a n)`, `(log b a)`, `(d/dx a)`,
+    /// one-argument functions named as in [`Expression::parse`] such as `(sin a)`,
+    /// `(piecewise (< c a) ...)` with one `(operator bound branch)` list per branch, and
+    /// `(symbol c)` for a symbol named `x` or `e`, which as atoms are the variable and
+    /// the constant.
+    pub fn from_sexpr(input: &str) -> Result<Expression, ParseError> {
+        let mut reader = Reader {
+            tokens: tokenize(input),
+            pos: 0,
+        };
+        let expr = reader.expr()?;
+        match reader.next() {
+            None => Ok(expr),
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+        }
+    }
+
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Expression::Constant(c) => write!(out, "{}", c).unwrap(),
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => write!(out, "(rational {} {})", n, d).unwrap(),
+            Expression::Pi => out.push_str("pi"),
+            Expression::E => out.push('e'),
+            Expression::Variable => out.push('x'),
+            // Written as a list when the bare atom would read back as `x` or `e`.
+            Expression::Symbol(name @ ('x' | 'e')) => write!(out, "(symbol {})", name).unwrap(),
+            Expression::Symbol(name) => out.push(*name),
+            Expression::Sum(a, b) => write_list(out, "+", &[a, b]),
+            Expression::Difference(a, b) => write_list(out, "-", &[a, b]),
+            Expression::Negate(a) => write_list(out, "-", &[a]),
+            Expression::Product(a, b) => write_list(out, "*", &[a, b]),
+            Expression::Quotient(a, b) => write_list(out, "/", &[a, b]),
+            Expression::Reciprocal(a) => write_list(out, "recip", &[a]),
+            Expression::Power(base, exp) => {
+                out.push_str("(^ ");
+                base.write_sexpr(out);
+                write!(out, " {})", exp).unwrap();
+            }
+            Expression::PowerExpr(base, exp) => write_list(out, "pow", &[base, exp]),
+            Expression::Root(a, n) => {
+                out.push_str("(root ");
+                a.write_sexpr(out);
+                write!(out, " {})", n).unwrap();
+            }
+            Expression::Sin(a) => write_list(out, "sin", &[a]),
+            Expression::Cos(a) => write_list(out, "cos", &[a]),
+            Expression::Tan(a) => write_list(out, "tan", &[a]),
+            Expression::Sec(a) => write_list(out, "sec", &[a]),
+            Expression::Csc(a) => write_list(out, "csc", &[a]),
+            Expression::Cot(a) => write_list(out, "cot", &[a]),
+            Expression::Atan(a) => write_list(out, "atan", &[a]),
+            Expression::Sinh(a) => write_list(out, "sinh", &[a]),
+            Expression::Cosh(a) => write_list(out, "cosh", &[a]),
+            Expression::Abs(a) => write_list(out, "abs", &[a]),
+            Expression::Sign(a) => write_list(out, "sign", &[a]),
+            Expression::Exp(a) => write_list(out, "exp", &[a]),
+            Expression::Ln(a) => write_list(out, "ln", &[a]),
+            Expression::Derivative(a) => write_list(out, "d/dx", &[a]),
+            Expression::Log(base, a) => {
+                write!(out, "(log {} ", base).unwrap();
+                a.write_sexpr(out);
+                out.push(')');
+            }
+            Expression::Piecewise(branches) => {
+                out.push_str("(piecewise");
+                for (condition, branch) in branches {
+                    let (operator, bound) = match *condition {
+                        Condition::Less(c) => ("<", c),
+                        Condition::LessOrEqual(c) => ("<=", c),
+                        Condition::Greater(c) => (">", c),
+                        Condition::GreaterOrEqual(c) => (">=", c),
+                    };
+                    write!(out, " ({} {} ", operator, bound).unwrap();
+                    branch.write_sexpr(out);
+                    out.push(')');
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn write_list(out: &mut String, head: &str, operands: &[&Rc<Expression>]) {
+    out.push('(');
+    out.push_str(head);
+    for operand in operands {
+        out.push(' ');
+        operand.write_sexpr(out);
+    }
+    out.push(')');
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Atom(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::LParen => String::from("("),
+            Token::RParen => String::from(")"),
+            Token::Atom(atom) => atom.clone(),
+        }
+    }
+}
+
+// Splits the input into parentheses and the whitespace-separated atoms between them,
+// each with its byte offset.
+fn tokenize(input: &str) -> Vec<(Token, usize)> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(pos, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            _ => {
+                let mut atom = String::new();
+                while let Some(&(_, d)) = chars.peek() {
+                    if d.is_whitespace() || d == '(' || d == ')' {
+                        break;
+                    }
+                    atom.push(d);
+                    chars.next();
+                }
+                tokens.push((Token::Atom(atom), pos));
+                continue;
+            }
+        };
+        chars.next();
+        tokens.push((token, pos));
+    }
+
+    tokens
+}
+
+struct Reader {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.next() {
+            Some((token, _)) if token == expected => Ok(()),
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    // A bare numeric atom, as taken by exponents, root degrees, logarithm bases and
+    // piecewise bounds.
+    fn number(&mut self) -> Result<f64, ParseError> {
+        match self.next() {
+            Some((Token::Atom(atom), _)) => {
+                atom.parse().map_err(|_| ParseError::InvalidNumber(atom))
+            }
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expression, ParseError> {
+        match self.next() {
+            Some((Token::Atom(atom), _)) => atom_expr(atom),
+            Some((Token::LParen, _)) => {
+                let expr = self.list()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    // The body of a list after its opening parenthesis, up to but not including the
+    // closing one.
+    fn list(&mut self) -> Result<Expression, ParseError> {
+        let head = match self.next() {
+            Some((Token::Atom(head), _)) => head,
+            Some((token, pos)) => return Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => return Err(ParseError::UnexpectedEnd),
+        };
+        Ok(match head.as_str() {
+            "+" => Expression::Sum(self.operand()?, self.operand()?),
+            "-" => {
+                let a = self.operand()?;
+                if self.peek() == Some(&Token::RParen) {
+                    Expression::Negate(a)
+                } else {
+                    Expression::Difference(a, self.operand()?)
+                }
+            }
+            "*" => Expression::Product(self.operand()?, self.operand()?),
+            "/" => Expression::Quotient(self.operand()?, self.operand()?),
+            "^" => Expression::Power(self.operand()?, self.number()?),
+            "pow" => Expression::PowerExpr(self.operand()?, self.operand()?),
+            "root" => Expression::Root(self.operand()?, self.number()?),
+            "log" => Expression::Log(self.number()?, self.operand()?),
+            "piecewise" => self.piecewise()?,
+            "symbol" => self.symbol()?,
+            #[cfg(feature = "rational")]
+            "rational" => self.rational()?,
+            _ => {
+                let function: fn(Rc<Expression>) -> Expression = match head.as_str() {
+                    "recip" => Expression::Reciprocal,
+                    "sin" => Expression::Sin,
+                    "cos" => Expression::Cos,
+                    "tan" => Expression::Tan,
+                    "sec" => Expression::Sec,
+                    "csc" => Expression::Csc,
+                    "cot" => Expression::Cot,
+                    "atan" => Expression::Atan,
+                    "sinh" => Expression::Sinh,
+                    "cosh" => Expression::Cosh,
+                    "abs" => Expression::Abs,
+                    "sign" => Expression::Sign,
+                    "exp" => Expression::Exp,
+                    "ln" => Expression::Ln,
+                    "d/dx" => Expression::Derivative,
+                    _ => return Err(ParseError::UnknownIdentifier(head)),
+                };
+                function(self.operand()?)
+            }
+        })
+    }
+
+    fn operand(&mut self) -> Result<Rc<Expression>, ParseError> {
+        self.expr().map(Rc::new)
+    }
+
+    fn symbol(&mut self) -> Result<Expression, ParseError> {
+        match self.next() {
+            Some((Token::Atom(atom), pos)) => {
+                let mut chars = atom.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(name), None) if name.is_alphabetic() => Ok(Expression::Symbol(name)),
+                    _ => Err(ParseError::UnexpectedToken(atom, pos)),
+                }
+            }
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn piecewise(&mut self) -> Result<Expression, ParseError> {
+        let mut branches = Vec::new();
+        while self.peek() == Some(&Token::LParen) {
+            self.next();
+            let condition: fn(f64) -> Condition = match self.next() {
+                Some((Token::Atom(operator), pos)) => match operator.as_str() {
+                    "<" => Condition::Less,
+                    "<=" => Condition::LessOrEqual,
+                    ">" => Condition::Greater,
+                    ">=" => Condition::GreaterOrEqual,
+                    _ => return Err(ParseError::UnexpectedToken(operator, pos)),
+                },
+                Some((token, pos)) => {
+                    return Err(ParseError::UnexpectedToken(token.describe(), pos));
+                }
+                None => return Err(ParseError::UnexpectedEnd),
+            };
+            let bound = self.number()?;
+            let branch = self.operand()?;
+            self.expect(Token::RParen)?;
+            branches.push((condition(bound), branch));
+        }
+        Ok(Expression::Piecewise(branches))
+    }
+
+    #[cfg(feature = "rational")]
+    fn rational(&mut self) -> Result<Expression, ParseError> {
+        let mut integer = || match self.next() {
+            Some((Token::Atom(atom), _)) => atom
+                .parse::<i64>()
+                .map_err(|_| ParseError::InvalidNumber(atom)),
+            Some((token, pos)) => Err(ParseError::UnexpectedToken(token.describe(), pos)),
+            None => Err(ParseError::UnexpectedEnd),
+        };
+        let numerator = integer()?;
+        match integer()? {
+            0 => Err(ParseError::InvalidNumber(String::from("0"))),
+            denominator => Ok(Expression::rational(numerator, denominator)),
+        }
+    }
+}
+
+fn atom_expr(atom: String) -> Result<Expression, ParseError> {
+    match atom.as_str() {
+        "x" => return Ok(Expression::Variable),
+        "pi" => return Ok(Expression::Pi),
+        "e" => return Ok(Expression::E),
+        _ => {}
+    }
+    let mut chars = atom.chars();
+    if let (Some(name), None) = (chars.next(), chars.next())
+        && name.is_alphabetic()
+    {
+        return Ok(Expression::Symbol(name));
+    }
+    match atom.parse() {
+        Ok(value) => Ok(Expression::Constant(value)),
+        Err(_) if atom.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) => {
+            Err(ParseError::InvalidNumber(atom))
+        }
+        Err(_) => Err(ParseError::UnknownIdentifier(atom)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_prefix_form() {
+        let expr = Expression::parse("x^2 - 4").unwrap();
+        assert_eq!(expr.to_sexpr(), "(- (^ x 2) 4)");
+    }
+
+    #[test]
+    fn round_trips() {
+        for input in [
+            "x^2 - 4",
+            "sin(x) / x",
+            "-(x + pi) * e",
+            "exp(2 * x) - ln(abs(x))",
+            "log2(x) + sqrt(x)",
+            "x^x",
+            "a * x + b",
+        ] {
+            let expr = Expression::parse(input).unwrap();
+            assert_eq!(
+                Expression::from_sexpr(&expr.to_sexpr()),
+                Ok(expr),
+                "{}",
+                input
+            );
+        }
+        let piecewise = Expression::piecewise([
+            (Condition::Less(0.0), -Expression::var()),
+            (Condition::GreaterOrEqual(0.0), Expression::var()),
+        ]);
+        assert_eq!(Expression::from_sexpr(&piecewise.to_sexpr()), Ok(piecewise));
+    }
+
+    #[test]
+    fn round_trips_symbols_and_non_finite_constants() {
+        for expr in [
+            Expression::Symbol('e'),
+            Expression::Symbol('x'),
+            Expression::Symbol('a'),
+            Expression::Constant(f64::INFINITY),
+            Expression::Constant(f64::NEG_INFINITY),
+            Expression::Constant(-0.0),
+        ] {
+            assert_eq!(
+                Expression::from_sexpr(&expr.to_sexpr()),
+                Ok(expr.clone()),
+                "{:?}",
+                expr
+            );
+        }
+        assert_eq!(Expression::Symbol('e').to_sexpr(), "(symbol e)");
+        let nan = Expression::from_sexpr(&Expression::Constant(f64::NAN).to_sexpr());
+        assert!(matches!(nan, Ok(Expression::Constant(c)) if c.is_nan()));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(
+            Expression::from_sexpr("(+ x"),
+            Err(ParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Expression::from_sexpr("(foo x)"),
+            Err(ParseError::UnknownIdentifier(String::from("foo")))
+        );
+    }
+}
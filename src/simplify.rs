@@ -0,0 +1,534 @@
+use std::rc::Rc;
+
+use crate::Expression;
+use crate::builder::monomial;
+#[cfg(feature = "rational")]
+use crate::rational;
+
+// Folds a subtree into a single constant when the result is a finite number, so
+// undefined operations like `1/0` or `ln(-1)` stay visible in the tree.
+fn fold(expr: Expression) -> Expression {
+    #[cfg(feature = "rational")]
+    if let Some(exact) = rational::fold(&expr) {
+        return exact;
+    }
+    match expr.constant_value() {
+        Some(value) if value.is_finite() => Expression::Constant(value),
+        _ => expr,
+    }
+}
+
+// A number leaf, which `fold` can combine with another.
+fn is_number(expr: &Expression) -> bool {
+    match expr {
+        Expression::Constant(_) => true,
+        #[cfg(feature = "rational")]
+        Expression::Rational(..) => true,
+        _ => false,
+    }
+}
+
+fn is_constant(expr: &Expression, value: f64) -> bool {
+    matches!(expr, Expression::Constant(c) if *c == value)
+}
+
+// Whether the expression is defined for every `x`, so that it times zero is zero
+// everywhere. Anything that can be undefined somewhere, like a quotient, a logarithm
+// or an even root, is not, since `0 * ln(x)` is still undefined for `x <= 0`.
+fn is_defined_everywhere(expr: &Expression) -> bool {
+    expr.iter_nodes().all(|node| match node {
+        Expression::Constant(c) => c.is_finite(),
+        Expression::Power(_, n) => *n >= 0.0 && n.fract() == 0.0,
+        Expression::Root(_, n) => n.fract() == 0.0 && n % 2.0 != 0.0,
+        Expression::Pi
+        | Expression::E
+        | Expression::Variable
+        | Expression::Symbol(_)
+        | Expression::Sum(..)
+        | Expression::Difference(..)
+        | Expression::Negate(_)
+        | Expression::Product(..)
+        | Expression::Sin(_)
+        | Expression::Cos(_)
+        | Expression::Atan(_)
+        | Expression::Sinh(_)
+        | Expression::Cosh(_)
+        | Expression::Abs(_)
+        | Expression::Sign(_)
+        | Expression::Exp(_) => true,
+        #[cfg(feature = "rational")]
+        Expression::Rational(..) => true,
+        _ => false,
+    })
+}
+
+impl Expression {
+    /// Returns an equivalent expression with identity operations removed and
+    /// constant-only subtrees folded into a single `Constant`.
+    ///
+    /// The result of each rewrite is simplified again, so the tree returned is already
+    /// fully simplified and a second pass leaves it unchanged.
+    ///
+    /// A product with zero only becomes zero when its other factor is defined for every
+    /// `x`, so `0 * sin(x)` is `0` while `0 * ln(x)` stays as it is, still undefined for
+    /// `x <= 0`.
+    pub fn simplify(&self) -> Expression {
+        match self {
+            Expression::Constant(_)
+            | Expression::Pi
+            | Expression::E
+            | Expression::Variable
+            | Expression::Symbol(_) => self.clone(),
+            #[cfg(feature = "rational")]
+            Expression::Rational(..) => self.clone(),
+            Expression::Sum(a, b) => match (a.simplify(), b.simplify()) {
+                (a, b) if is_number(&a) && is_number(&b) => {
+                    fold(Expression::Sum(Rc::new(a), Rc::new(b)))
+                }
+                // x + 0 = x, 0 + x = x
+                (a, b) if b.is_zero() => a,
+                (a, b) if a.is_zero() => b,
+                // x + -y = x - y
+                (a, Expression::Negate(ref b)) => Expression::Difference(Rc::new(a), b.clone()),
+                (a, b) => Expression::Sum(Rc::new(a), Rc::new(b)),
+            },
+            Expression::Difference(a, b) => match (a.simplify(), b.simplify()) {
+                (a, b) if is_number(&a) && is_number(&b) => {
+                    fold(Expression::Difference(Rc::new(a), Rc::new(b)))
+                }
+                // x - 0 = x, 0 - x = -x
+                (a, b) if b.is_zero() => a,
+                (a, b) if a.is_zero() => Expression::Negate(Rc::new(b)).simplify(),
+                // x - -y = x + y
+                (a, Expression::Negate(ref b)) => Expression::Sum(Rc::new(a), b.clone()),
+                (a, b) => Expression::Difference(Rc::new(a), Rc::new(b)),
+            },
+            Expression::Negate(a) => match a.simplify() {
+                Expression::Constant(c) => Expression::Constant(-c),
+                #[cfg(feature = "rational")]
+                Expression::Rational(n, d) => rational::negate(n, d),
+                // -(-x) = x
+                Expression::Negate(ref inner) => (**inner).clone(),
+                a => Expression::Negate(Rc::new(a)),
+            },
+            Expression::Product(a, b) => match (a.simplify(), b.simplify()) {
+                (a, b) if is_number(&a) && is_number(&b) => {
+                    fold(Expression::Product(Rc::new(a), Rc::new(b)))
+                }
+                // x * 0 = 0 wherever x is defined, so only where that is everywhere
+                (a, b) if a.is_zero() && is_defined_everywhere(&b) => Expression::zero(),
+                (a, b) if b.is_zero() && is_defined_everywhere(&a) => Expression::zero(),
+                // x * 1 = x
+                (a, b) if b.is_one() => a,
+                (a, b) if a.is_one() => b,
+                // x * -1 = -x
+                (a, b) if is_constant(&b, -1.0) => Expression::Negate(Rc::new(a)).simplify(),
+                (a, b) if is_constant(&a, -1.0) => Expression::Negate(Rc::new(b)).simplify(),
+                // c * -x = (-c) * x
+                (Expression::Constant(c), Expression::Negate(ref b)) => {
+                    Expression::Product(Rc::new(Expression::Constant(-c)), b.clone()).simplify()
+                }
+                // Keep constant factors together on the left: c1 * (c2 * x) = (c1*c2) * x
+                (c1, Expression::Product(ref c2, ref rest)) if is_number(&c1) && is_number(c2) => {
+                    let c = fold(Expression::Product(Rc::new(c1), c2.clone()));
+                    Expression::Product(Rc::new(c), rest.clone()).simplify()
+                }
+                (a, b) if is_number(&b) => Expression::Product(Rc::new(b), Rc::new(a)).simplify(),
+                (a, b) => Expression::Product(Rc::new(a), Rc::new(b)),
+            },
+            Expression::Quotient(a, b) => match (a.simplify(), b.simplify()) {
+                (a, b) if is_number(&a) && is_number(&b) => {
+                    fold(Expression::Quotient(Rc::new(a), Rc::new(b)))
+                }
+                // x / 1 = x
+                (a, b) if b.is_one() => a,
+                // x / (1/y) = x * y, x / y^-n = x * y^n
+                (a, Expression::Reciprocal(ref b)) => {
+                    Expression::Product(Rc::new(a), b.clone()).simplify()
+                }
+                (a, Expression::Power(ref b, n)) if n < 0.0 => {
+                    let power = Expression::Power(b.clone(), -n);
+                    Expression::Product(Rc::new(a), Rc::new(power)).simplify()
+                }
+                (a, b) => Expression::Quotient(Rc::new(a), Rc::new(b)),
+            },
+            Expression::Power(base, exp) => match (base.simplify(), *exp) {
+                // x^0 = 1, x^1 = x
+                (_, 0.0) => Expression::one(),
+                (base, 1.0) => base,
+                (base @ Expression::Constant(_), exp) => {
+                    fold(Expression::Power(Rc::new(base), exp))
+                }
+                // (1/x)^n = x^-n
+                (Expression::Reciprocal(ref base), exp) => {
+                    Expression::Power(base.clone(), -exp).simplify()
+                }
+                (base, exp) => Expression::Power(Rc::new(base), exp),
+            },
+            Expression::PowerExpr(base, exp) => match (base.simplify(), exp.simplify()) {
+                // A constant exponent is an ordinary power.
+                (base, Expression::Constant(exp)) => {
+                    Expression::Power(Rc::new(base), exp).simplify()
+                }
+                (base, exp) => Expression::PowerExpr(Rc::new(base), Rc::new(exp)),
+            },
+            Expression::Root(a, n) => {
+                let n = *n;
+                simplify_unary(a, move |a| Expression::Root(a, n))
+            }
+            Expression::Sin(a) => simplify_unary(a, Expression::Sin),
+            Expression::Cos(a) => simplify_unary(a, Expression::Cos),
+            Expression::Tan(a) => simplify_unary(a, Expression::Tan),
+            Expression::Reciprocal(a) => simplify_unary(a, Expression::Reciprocal),
+            Expression::Sec(a) => simplify_unary(a, Expression::Sec),
+            Expression::Csc(a) => simplify_unary(a, Expression::Csc),
+            Expression::Cot(a) => simplify_unary(a, Expression::Cot),
+            Expression::Atan(a) => simplify_unary(a, Expression::Atan),
+            Expression::Sinh(a) => simplify_unary(a, Expression::Sinh),
+            Expression::Cosh(a) => simplify_unary(a, Expression::Cosh),
+            Expression::Abs(a) => simplify_unary(a, Expression::Abs),
+            Expression::Sign(a) => simplify_unary(a, Expression::Sign),
+            Expression::Exp(a) => simplify_unary(a, Expression::Exp),
+            Expression::Ln(a) => simplify_unary(a, Expression::Ln),
+            Expression::Derivative(a) => simplify_unary(a, Expression::Derivative),
+            Expression::Piecewise(branches) => Expression::Piecewise(
+                branches
+                    .iter()
+                    .map(|(condition, branch)| (*condition, Rc::new(branch.simplify())))
+                    .collect(),
+            ),
+            Expression::Log(base, a) => {
+                let base = *base;
+                simplify_unary(a, move |a| Expression::Log(base, a))
+            }
+        }
+    }
+
+    /// Collapses every subtree that does not depend on a variable into a single
+    /// `Constant`, leaving the rest of the tree as it is.
+    ///
+    /// Unlike [`Expression::simplify`] this applies no identities, so it never changes
+    /// the value of the expression. Subtrees without a finite value, like `1/0`, are
+    /// kept so they stay visible.
+    pub fn fold_constants(&self) -> Expression {
+        let folded = self.map_children(Expression::fold_constants);
+        // Children have already been folded, so only all-constant ones can fold further.
+        if folded
+            .children()
+            .all(|child| matches!(child, Expression::Constant(_)))
+            && folded.is_constant()
+        {
+            fold(folded)
+        } else {
+            folded
+        }
+    }
+
+    /// Orders the two operands of every sum and product deterministically, so that
+    /// `a + b` and `b + a` give the same tree and compare equal.
+    ///
+    /// Operands are ordered by how they display, which leaves the value unchanged.
+    /// Sums and products are not reassociated, so `(a + b) + c` and `a + (b + c)` stay
+    /// different.
+    pub fn canonicalize(&self) -> Expression {
+        let ordered = |a: &Rc<Expression>, b: &Rc<Expression>| {
+            if a.to_string() <= b.to_string() {
+                (a.clone(), b.clone())
+            } else {
+                (b.clone(), a.clone())
+            }
+        };
+        let canonical = self.map_children(Expression::canonicalize);
+        match &canonical {
+            Expression::Sum(a, b) => {
+                let (a, b) = ordered(a, b);
+                Expression::Sum(a, b)
+            }
+            Expression::Product(a, b) => {
+                let (a, b) = ordered(a, b);
+                Expression::Product(a, b)
+            }
+            _ => canonical,
+        }
+    }
+
+    /// Gathers the monomials `c * x^n` of a sum into one term per degree, written from
+    /// the highest degree down, so `x^2 + 3*x^2 - x^2` becomes `3 * x^2`.
+    ///
+    /// Nested sums, differences and negations are flattened first. Terms that are not
+    /// monomials, like `sin(x)`, are kept as they are after the polynomial part.
+    pub fn collect_like_terms(&self) -> Expression {
+        let mut terms = Vec::new();
+        flatten_terms(self, false, &mut terms);
+
+        let mut monomials: Vec<(f64, f64)> = Vec::new();
+        let mut others = Vec::new();
+        for (negated, term) in terms {
+            let Some((degree, coefficient)) = as_monomial(term) else {
+                others.push((negated, term.clone()));
+                continue;
+            };
+            let coefficient = if negated { -coefficient } else { coefficient };
+            match monomials.iter_mut().find(|(d, _)| *d == degree) {
+                Some((_, total)) => *total += coefficient,
+                None => monomials.push((degree, coefficient)),
+            }
+        }
+        monomials.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut collected: Option<Expression> = None;
+        for (degree, coefficient) in monomials {
+            if coefficient == 0.0 {
+                continue;
+            }
+            collected = Some(match collected {
+                None => monomial(degree, coefficient),
+                Some(sum) if coefficient < 0.0 => sum - monomial(degree, -coefficient),
+                Some(sum) => sum + monomial(degree, coefficient),
+            });
+        }
+        for (negated, term) in others {
+            collected = Some(add_term(collected, negated, term));
+        }
+        collected.unwrap_or_else(Expression::zero)
+    }
+
+    /// Multiplies out products and whole-number powers of sums and differences, then
+    /// gathers the terms with [`Expression::collect_like_terms`], so `(x+1)*(x-1)`
+    /// becomes `x^2 - 1`.
+    ///
+    /// Function arguments are expanded too, so `sin((x+1)^2)` becomes
+    /// `sin(x^2 + 2*x + 1)`. Products of monomials are multiplied into a single
+    /// monomial, and any other product of terms, like `x * sin(x)`, is kept as it is.
+    pub fn expand(&self) -> Expression {
+        let expanded = self.map_children(Expression::expand);
+        match &expanded {
+            Expression::Product(a, b) => multiply_out(a, b).collect_like_terms(),
+            Expression::Power(base, n)
+                if n.fract() == 0.0
+                    && *n >= 2.0
+                    && matches!(**base, Expression::Sum(..) | Expression::Difference(..)) =>
+            {
+                let mut power = (**base).clone();
+                for _ in 1..*n as u32 {
+                    power = multiply_out(&power, base).collect_like_terms();
+                }
+                power
+            }
+            Expression::Sum(..) | Expression::Difference(..) | Expression::Negate(_) => {
+                expanded.collect_like_terms()
+            }
+            _ => expanded,
+        }
+    }
+}
+
+// `sum + term` or `sum - term`, or the term alone if there is no sum yet.
+fn add_term(sum: Option<Expression>, negated: bool, term: Expression) -> Expression {
+    match (sum, negated) {
+        (None, false) => term,
+        (None, true) => -term,
+        (Some(sum), false) => sum + term,
+        (Some(sum), true) => sum - term,
+    }
+}
+
+// Multiplies every term of `a` by every term of `b`, with the terms of nested sums,
+// differences and negations found by `flatten_terms`.
+fn multiply_out(a: &Expression, b: &Expression) -> Expression {
+    let (mut left, mut right) = (Vec::new(), Vec::new());
+    flatten_terms(a, false, &mut left);
+    flatten_terms(b, false, &mut right);
+
+    let mut product = None;
+    for &(left_negated, left_term) in &left {
+        for &(right_negated, right_term) in &right {
+            let term = match (as_monomial(left_term), as_monomial(right_term)) {
+                (Some((da, ca)), Some((db, cb))) => monomial(da + db, ca * cb),
+                _ => Expression::Product(Rc::new(left_term.clone()), Rc::new(right_term.clone())),
+            };
+            product = Some(add_term(product, left_negated != right_negated, term));
+        }
+    }
+    product.unwrap_or_else(Expression::zero)
+}
+
+// Appends the terms of nested sums, differences and negations to `terms`, each with
+// whether it is subtracted.
+fn flatten_terms<'a>(expr: &'a Expression, negated: bool, terms: &mut Vec<(bool, &'a Expression)>) {
+    match expr {
+        Expression::Sum(a, b) => {
+            flatten_terms(a, negated, terms);
+            flatten_terms(b, negated, terms);
+        }
+        Expression::Difference(a, b) => {
+            flatten_terms(a, negated, terms);
+            flatten_terms(b, !negated, terms);
+        }
+        Expression::Negate(a) => flatten_terms(a, !negated, terms),
+        term => terms.push((negated, term)),
+    }
+}
+
+// The degree and coefficient of `c * x^n`, with the constant factor on either side.
+fn as_monomial(term: &Expression) -> Option<(f64, f64)> {
+    let power = |expr: &Expression| match expr {
+        Expression::Variable => Some(1.0),
+        Expression::Power(base, n) if matches!(**base, Expression::Variable) => Some(*n),
+        _ => None,
+    };
+    match term {
+        Expression::Constant(c) => Some((0.0, *c)),
+        Expression::Product(a, b) => match (&**a, &**b) {
+            (Expression::Constant(c), other) | (other, Expression::Constant(c)) => {
+                Some((power(other)?, *c))
+            }
+            _ => None,
+        },
+        other => Some((power(other)?, 1.0)),
+    }
+}
+
+fn simplify_unary(arg: &Expression, function: impl Fn(Rc<Expression>) -> Expression) -> Expression {
+    match arg.simplify() {
+        arg @ Expression::Constant(_) => fold(function(Rc::new(arg))),
+        arg => function(Rc::new(arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Expression {
+        Expression::parse(input).unwrap()
+    }
+
+    #[test]
+    fn simplifies_a_derivative() {
+        let derivative = parse("x^2 - 4").differentiate().unwrap().simplify();
+        assert_eq!(
+            derivative,
+            Expression::Product(
+                Rc::new(Expression::Constant(2.0)),
+                Rc::new(Expression::Variable)
+            )
+        );
+    }
+
+    #[test]
+    fn reciprocals_in_denominators() {
+        assert_eq!(parse("1 / recip(x)").simplify(), parse("x"));
+        assert_eq!(parse("2 / x^-3").simplify(), parse("2 * x^3"));
+        assert_eq!(parse("recip(x)^2").simplify(), parse("x^-2"));
+    }
+
+    #[test]
+    fn folds_constant_chains() {
+        let expr = Expression::Sum(
+            Rc::new(Expression::Power(Rc::new(Expression::Constant(2.0)), 3.0)),
+            Rc::new(Expression::Constant(1.0)),
+        );
+        assert_eq!(expr.fold_constants(), Expression::Constant(9.0));
+        assert_eq!(parse("x + 2^3").fold_constants(), parse("x + 8"));
+    }
+
+    #[test]
+    fn collects_like_terms() {
+        assert_eq!(
+            parse("x^2 + 3*x^2 - x^2").collect_like_terms().to_string(),
+            "3 * x^2"
+        );
+        assert_eq!(
+            parse("x + sin(x) + 2*x - 4")
+                .collect_like_terms()
+                .to_string(),
+            "3 * x - 4 + sin(x)"
+        );
+    }
+
+    #[test]
+    fn canonical_ordering() {
+        let (a, b) = (parse("sin(x)"), parse("x^2"));
+        let ab = (a.clone() + b.clone()).canonicalize();
+        assert_eq!(ab, (b.clone() + a.clone()).canonicalize());
+        assert_eq!(
+            (a.clone() * b.clone()).canonicalize(),
+            (b * a).canonicalize()
+        );
+        assert_eq!(ab.evaluate(0.7), parse("sin(x) + x^2").evaluate(0.7));
+    }
+
+    #[test]
+    fn simplifies_noisy_derivatives() {
+        // A derivative reached by a longer route picks up floating noise, which the
+        // tolerant comparison looks past.
+        let direct = parse("x^3").differentiate().unwrap().simplify();
+        let noisy = parse("x^3 * 1.0000000000001")
+            .differentiate()
+            .unwrap()
+            .simplify();
+        assert_ne!(direct, noisy);
+        assert!(
+            direct.structurally_eq_within(&noisy, 1e-9),
+            "{} vs {}",
+            direct,
+            noisy
+        );
+    }
+
+    #[test]
+    fn expands_products_of_sums() {
+        let product = parse("(x + 1) * (x - 1)");
+        let expanded = product.expand();
+        assert_eq!(expanded.to_string(), "x^2 - 1");
+        for x in [-2.0, -0.5, 0.0, 1.5, 3.0] {
+            assert_eq!(expanded.evaluate(x), product.evaluate(x));
+        }
+        assert_eq!(parse("(x + 2)^2").expand().to_string(), "x^2 + 4 * x + 4");
+    }
+
+    #[test]
+    fn simplify_is_idempotent() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        // NaN constants never compare equal, so trees holding one are left out.
+        let has_nan = |expr: &Expression| {
+            expr.iter_nodes()
+                .any(|node| matches!(node, Expression::Constant(c) if c.is_nan()))
+        };
+        let mut rng = StdRng::seed_from_u64(112);
+        for _ in 0..5000 {
+            let expr = Expression::random(6, &mut rng);
+            let once = expr.simplify();
+            if has_nan(&expr) || has_nan(&once) {
+                continue;
+            }
+            assert_eq!(once.simplify(), once, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn keeps_undefined_factors_of_zero() {
+        assert_eq!(parse("0 * sin(x) + x").simplify(), Expression::Variable);
+        assert_eq!(parse("x^2 * 0").simplify(), Expression::zero());
+        // Each stays undefined at the point given with it.
+        for (input, x) in [
+            ("0 * ln(x)", 0.0),
+            ("0 * (1 / x)", 0.0),
+            ("0 * sqrt(x)", -1.0),
+            ("tan(x) * 0", std::f64::consts::FRAC_PI_2),
+        ] {
+            let simplified = parse(input).simplify();
+            assert!(!simplified.is_zero(), "{} gave {}", input, simplified);
+            assert!(simplified.evaluate(x).is_nan(), "{} at {}", simplified, x);
+        }
+    }
+
+    #[test]
+    fn collapses_nested_identities_in_one_pass() {
+        let expr = parse("((((x * 1 + 0) ^ 1) * 1) / 1 - 0) * (2 - 1)");
+        assert_eq!(expr.simplify(), Expression::Variable);
+    }
+}
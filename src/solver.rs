@@ -0,0 +1,2898 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::expression::trend_at_infinity;
+use crate::{Differentiable, Expression, LhopitalError, ParseError, SolveConfig};
+
+/// Magnitude of `x` at which expressions are sampled for limits at infinity.
+pub const INFINITY_PROXY: f64 = 1e8;
+
+/// Default magnitude below which a value counts as zero when detecting `0/0`.
+///
+/// Suits problems whose values at the limit point are of order one; problems at
+/// other scales should pass their own tolerance.
+pub const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+/// Offset from the limit point used when the solver falls back to sampling numerically.
+pub const NUMERIC_EPSILON: f64 = 1e-6;
+
+/// Default magnitude past which a sampled value counts as having overflowed, close
+/// enough to `f64::MAX` that only values about to become infinite exceed it.
+pub const DEFAULT_MAX_MAGNITUDE: f64 = 1e300;
+
+/// Default cap on how many times L'Hôpital's Rule is applied.
+///
+/// Some quotients never reach a determinate form, e.g. when the derivatives grow
+/// without simplifying, so the solver must stop somewhere. Limits met in practice
+/// need only a handful of applications.
+pub const DEFAULT_MAX_ITERATIONS: u32 = 20;
+
+// Values at least as large as the infinity proxy are treated as unbounded, so that
+// e.g. `x^2` sampled at the proxy counts as tending to infinity.
+fn is_unbounded(value: f64) -> bool {
+    value.is_infinite() || value.abs() >= INFINITY_PROXY
+}
+
+// Both values are unbounded in the same way. A large finite value over an actual
+// infinity is already determinate, and differentiating it risks `inf * 0` terms.
+fn both_unbounded(num: f64, den: f64) -> bool {
+    is_unbounded(num) && is_unbounded(den) && num.is_infinite() == den.is_infinite()
+}
+
+/// The side a finite limit point is approached from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Both,
+}
+
+/// The point a limit is taken at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitPoint {
+    /// A two-sided limit at a finite point.
+    Finite(f64),
+    /// The limit from below a finite point.
+    Left(f64),
+    /// The limit from above a finite point.
+    Right(f64),
+    PosInfinity,
+    NegInfinity,
+}
+
+impl LimitPoint {
+    /// A finite limit point approached from `direction`.
+    pub fn approaching(at: f64, direction: Direction) -> Self {
+        match direction {
+            Direction::Left => LimitPoint::Left(at),
+            Direction::Right => LimitPoint::Right(at),
+            Direction::Both => LimitPoint::Finite(at),
+        }
+    }
+
+    /// The side the point is approached from. Infinite points can only be approached
+    /// from one side: `+∞` from the left and `-∞` from the right.
+    pub fn direction(&self) -> Direction {
+        match self {
+            LimitPoint::Finite(_) => Direction::Both,
+            LimitPoint::Left(_) | LimitPoint::PosInfinity => Direction::Left,
+            LimitPoint::Right(_) | LimitPoint::NegInfinity => Direction::Right,
+        }
+    }
+
+    // Rejects NaN points, which every comparison in the solver would silently fail
+    // on, and turns an infinity held by a finite variant into the infinite point.
+    fn validated(self) -> Result<Self, LhopitalError> {
+        match self {
+            LimitPoint::Finite(at) | LimitPoint::Left(at) if at == f64::INFINITY => {
+                Ok(LimitPoint::PosInfinity)
+            }
+            LimitPoint::Finite(at) | LimitPoint::Right(at) if at == f64::NEG_INFINITY => {
+                Ok(LimitPoint::NegInfinity)
+            }
+            LimitPoint::Finite(at) | LimitPoint::Left(at) | LimitPoint::Right(at)
+                if !at.is_finite() =>
+            {
+                Err(LhopitalError::InvalidLimitPoint(self.to_string()))
+            }
+            point => Ok(point),
+        }
+    }
+
+    /// The value of `x` the expressions are evaluated at for this point.
+    pub fn sample_point(&self) -> f64 {
+        match self {
+            LimitPoint::Finite(at) | LimitPoint::Left(at) | LimitPoint::Right(at) => *at,
+            LimitPoint::PosInfinity => INFINITY_PROXY,
+            LimitPoint::NegInfinity => -INFINITY_PROXY,
+        }
+    }
+}
+
+impl From<f64> for LimitPoint {
+    fn from(at: f64) -> Self {
+        if at == f64::INFINITY {
+            LimitPoint::PosInfinity
+        } else if at == f64::NEG_INFINITY {
+            LimitPoint::NegInfinity
+        } else {
+            LimitPoint::Finite(at)
+        }
+    }
+}
+
+impl fmt::Display for LimitPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitPoint::Finite(at) => write!(f, "{}", at),
+            LimitPoint::Left(at) => write!(f, "{}-", at),
+            LimitPoint::Right(at) => write!(f, "{}+", at),
+            LimitPoint::PosInfinity => write!(f, "∞"),
+            LimitPoint::NegInfinity => write!(f, "-∞"),
+        }
+    }
+}
+
+/// Parses a limit point in the form it is displayed in: a number, `inf`, `-inf`, `∞`
+/// or `-∞`, with a trailing `-` or `+` making a finite point one-sided.
+impl FromStr for LimitPoint {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidLimitPoint(s.to_string());
+        let number = |s: &str| match s {
+            "∞" => Ok(f64::INFINITY),
+            "-∞" => Ok(f64::NEG_INFINITY),
+            s => s
+                .parse::<f64>()
+                .ok()
+                .filter(|at| !at.is_nan())
+                .ok_or_else(invalid),
+        };
+        let one_sided = |value: f64, point: fn(f64) -> LimitPoint| {
+            if value.is_finite() {
+                Ok(point(value))
+            } else {
+                Err(invalid())
+            }
+        };
+        if let Some(at) = s.strip_suffix('-').filter(|at| !at.is_empty()) {
+            return one_sided(number(at)?, LimitPoint::Left);
+        }
+        if let Some(at) = s.strip_suffix('+').filter(|at| !at.is_empty()) {
+            return one_sided(number(at)?, LimitPoint::Right);
+        }
+        number(s).map(LimitPoint::from)
+    }
+}
+
+// Samples `f` `epsilon` beside a finite limit point, on the side(s) the point is
+// approached from, requiring both sides to agree for a two-sided limit. Limits at
+// infinity are sampled at the infinity proxy.
+fn sample_beside(
+    at: LimitPoint,
+    epsilon: f64,
+    f: impl Fn(f64) -> f64,
+) -> Result<f64, LhopitalError> {
+    let x = at.sample_point();
+    if let LimitPoint::PosInfinity | LimitPoint::NegInfinity = at {
+        return Ok(f(x));
+    }
+
+    let left = f(x - epsilon);
+    let right = f(x + epsilon);
+    match at.direction() {
+        Direction::Left => Ok(left),
+        Direction::Right => Ok(right),
+        Direction::Both => {
+            // Smooth functions still differ by O(epsilon) across the point.
+            let scale = left.abs().max(right.abs()).max(1.0);
+            if left.is_nan() && right.is_nan() {
+                Err(LhopitalError::Undefined)
+            } else if (left - right).abs() <= epsilon.sqrt() * scale {
+                Ok((left + right) / 2.0)
+            } else {
+                Err(LhopitalError::LimitDoesNotExist { left, right })
+            }
+        }
+    }
+}
+
+// Constants limits commonly turn out to be, checked in order by `recognize_constant`.
+const NOTABLE_CONSTANTS: &[(&str, f64)] = &[
+    ("π", std::f64::consts::PI),
+    ("π/2", std::f64::consts::FRAC_PI_2),
+    ("π/4", std::f64::consts::FRAC_PI_4),
+    ("e", std::f64::consts::E),
+    ("1/e", 1.0 / std::f64::consts::E),
+    ("1/2", 0.5),
+    ("1/3", 1.0 / 3.0),
+    ("ln(2)", std::f64::consts::LN_2),
+    ("ln(10)", std::f64::consts::LN_10),
+    ("√2", std::f64::consts::SQRT_2),
+    ("1/√2", std::f64::consts::FRAC_1_SQRT_2),
+];
+
+/// Names the notable constant within `tol` of `value`, such as `"e"`, `"π"`, `"1/2"`
+/// or `"ln(2)"`, for annotating a limit found numerically. Returns `None` if `value`
+/// is close to none of them.
+pub fn recognize_constant(value: f64, tol: f64) -> Option<&'static str> {
+    NOTABLE_CONSTANTS
+        .iter()
+        .find(|(_, constant)| (value - constant).abs() <= tol)
+        .map(|(name, _)| *name)
+}
+
+/// Numerically estimates the limit of `numerator / denominator` by evaluating the
+/// quotient `epsilon` away from `at` on the side(s) it is approached from.
+///
+/// For a two-sided limit both sides are sampled and must agree, otherwise
+/// [`LhopitalError::LimitDoesNotExist`] is returned.
+pub fn numeric_limit(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: impl Into<LimitPoint>,
+    epsilon: f64,
+) -> Result<f64, LhopitalError> {
+    sample_beside(at.into(), epsilon, |x| {
+        numerator.evaluate(x) / denominator.evaluate(x)
+    })
+}
+
+/// Evaluates `numerator / denominator` at `at + offset` for each of `offsets`,
+/// returning `(x, value)` pairs in increasing order of offset.
+///
+/// Repeated offsets are sampled once, with `-0.0` counted as `0.0`, and the order
+/// never depends on the order the offsets were given in, so the same offsets always
+/// give identical output, e.g. for snapshot tests of a sampled approach. NaN offsets
+/// sort last.
+pub fn sample_around(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: f64,
+    offsets: &[f64],
+) -> Vec<(f64, f64)> {
+    let mut offsets: Vec<f64> = offsets.iter().map(|offset| offset + 0.0).collect();
+    offsets.sort_by(f64::total_cmp);
+    offsets.dedup_by(|a, b| a.total_cmp(b).is_eq());
+    offsets
+        .into_iter()
+        .map(|offset| {
+            let x = at + offset;
+            (x, numerator.evaluate(x) / denominator.evaluate(x))
+        })
+        .collect()
+}
+
+/// A table of `numerator / denominator` approaching `at` from both sides, as
+/// `(x, value)` pairs at `at - offset` and `at + offset` for each of `offsets`, in
+/// increasing order of `x`. A zero offset is skipped, since the point itself is
+/// usually where the quotient is undefined.
+///
+/// For `(x^2 - 4) / (x - 2)` at 2 with offsets 0.1 and 0.01 the values are 3.9, 3.99,
+/// 4.01 and 4.1, closing in on the limit 4.
+pub fn limit_table(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: f64,
+    offsets: &[f64],
+) -> Vec<(f64, f64)> {
+    let offsets: Vec<f64> = offsets
+        .iter()
+        .filter(|offset| **offset != 0.0)
+        .flat_map(|offset| [-offset.abs(), offset.abs()])
+        .collect();
+    sample_around(numerator, denominator, at, &offsets)
+}
+
+// The points `epsilon` away from `at` on each side it is approached from, or the
+// sample point itself for a limit at infinity.
+fn points_beside(at: LimitPoint, epsilon: f64) -> Vec<f64> {
+    let x = at.sample_point();
+    match (at, at.direction()) {
+        (LimitPoint::PosInfinity | LimitPoint::NegInfinity, _) => vec![x],
+        (_, Direction::Left) => vec![x - epsilon],
+        (_, Direction::Right) => vec![x + epsilon],
+        (_, Direction::Both) => vec![x - epsilon, x + epsilon],
+    }
+}
+
+// Whether two expressions are the same function up to the order of the operands of
+// sums and products. Counting nodes first keeps `canonicalize` off pairs that differ.
+fn same_function(a: &Expression, b: &Expression) -> bool {
+    a == b || (a.node_count() == b.node_count() && a.canonicalize() == b.canonicalize())
+}
+
+// Samples the current `numerator / denominator` beside the limit point when the
+// expressions cannot be evaluated at the point itself. A side only counts if the
+// original quotient is defined there, since differentiating can remove
+// singularities from the domain.
+fn numeric_fallback<T: Differentiable>(
+    original: (&T, &T),
+    current: (&T, &T),
+    at: LimitPoint,
+    epsilon: f64,
+) -> Result<f64, LhopitalError> {
+    sample_beside(at, epsilon, |x| {
+        if (original.0.evaluate(x) / original.1.evaluate(x)).is_nan() {
+            f64::NAN
+        } else {
+            current.0.evaluate(x) / current.1.evaluate(x)
+        }
+    })
+}
+
+/// Rewrites an expression in the `0·∞` or `∞-∞` form at `at` as a quotient, returned
+/// as `(numerator, denominator)`: a product `f * g` with `f -> 0` becomes `g / (1/f)`,
+/// and a difference of unbounded terms is combined with [`combine_fractions`].
+/// Returns `None` for any other expression.
+pub fn to_quotient_form(
+    expression: &Expression,
+    at: impl Into<LimitPoint>,
+) -> Option<(Expression, Expression)> {
+    let x = at.into().sample_point();
+    let tends_to_zero = |e: &Expression| e.evaluate(x).abs() < DEFAULT_TOLERANCE;
+    let tends_to_infinity = |e: &Expression| match e {
+        // A quotient evaluates to NaN over an exact zero, so check its parts instead.
+        Expression::Quotient(num, den) => {
+            let num_val = num.evaluate(x);
+            is_unbounded(num_val / den.evaluate(x))
+                || (num_val.abs() >= DEFAULT_TOLERANCE && tends_to_zero(den))
+        }
+        Expression::Reciprocal(den) => is_unbounded(1.0 / den.evaluate(x)) || tends_to_zero(den),
+        e => is_unbounded(e.evaluate(x)),
+    };
+
+    match expression {
+        Expression::Product(a, b) => {
+            let (zero, infinite) = if tends_to_zero(a) && tends_to_infinity(b) {
+                (a, b)
+            } else if tends_to_zero(b) && tends_to_infinity(a) {
+                (b, a)
+            } else {
+                return None;
+            };
+            Some(((**infinite).clone(), Expression::Reciprocal(zero.clone())))
+        }
+        Expression::Difference(a, b) if tends_to_infinity(a) && tends_to_infinity(b) => {
+            Some(combined_parts(a, b))
+        }
+        _ => None,
+    }
+}
+
+/// Combines a difference of fractions `a/b - c/d` over a common denominator into
+/// `(a*d - c*b) / (b*d)`. A term that is not a quotient counts as being over 1.
+/// Returns `None` if the expression is not a difference.
+pub fn combine_fractions(expression: &Expression) -> Option<Expression> {
+    let Expression::Difference(left, right) = expression else {
+        return None;
+    };
+    let (num, den) = combined_parts(left, right);
+    Some(num / den)
+}
+
+fn combined_parts(left: &Expression, right: &Expression) -> (Expression, Expression) {
+    let parts = |e: &Expression| match e {
+        Expression::Quotient(num, den) => ((**num).clone(), (**den).clone()),
+        other => (other.clone(), Expression::Constant(1.0)),
+    };
+    let (a, b) = parts(left);
+    let (c, d) = parts(right);
+    (a * &d - c * &b, b * d)
+}
+
+/// An indeterminate form found at the limit point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedForm {
+    ZeroOverZero,
+    InfOverInf,
+    /// A product `0·∞`, rewritten into a quotient before the rule was applied.
+    ZeroTimesInf,
+    /// A difference `∞-∞`, rewritten into a quotient before the rule was applied.
+    InfMinusInf,
+}
+
+impl fmt::Display for DetectedForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectedForm::ZeroOverZero => write!(f, "0/0"),
+            DetectedForm::InfOverInf => write!(f, "∞/∞"),
+            DetectedForm::ZeroTimesInf => write!(f, "0·∞"),
+            DetectedForm::InfMinusInf => write!(f, "∞-∞"),
+        }
+    }
+}
+
+/// One iteration of the solver: the quotient it looked at and its value at the limit point.
+#[derive(Debug, Clone)]
+pub struct Step<T = Expression> {
+    pub numerator: T,
+    pub denominator: T,
+    pub numerator_value: f64,
+    pub denominator_value: f64,
+    /// The indeterminate form of this quotient at the limit point, or `None` if it is
+    /// determinate. The first step of a rewritten input reports the original `0·∞` or
+    /// `∞-∞` form. A quotient with a part that overflows to infinity reports `∞/∞`,
+    /// although its value is read off directly rather than by differentiating.
+    pub form: Option<DetectedForm>,
+}
+
+/// The outcome of a traced solve.
+#[derive(Debug, Clone)]
+pub struct LhopitalResult<T = Expression> {
+    /// The limit.
+    pub value: f64,
+    /// Every quotient the solver evaluated, starting with the input.
+    pub iterations: Vec<Step<T>>,
+    /// How many times L'Hôpital's Rule was applied, i.e. the number of
+    /// differentiations before a determinate form was reached.
+    pub iterations_applied: u32,
+    /// The numerator of the quotient the limit was finally read off, after every
+    /// differentiation.
+    pub final_numerator: T,
+    /// The denominator of the quotient the limit was finally read off.
+    pub final_denominator: T,
+    /// A numeric estimate of the original quotient's limit, present only when it
+    /// disagrees with `value`. This usually points at a faulty differentiation rule.
+    pub numeric_mismatch: Option<f64>,
+}
+
+/// Computes the limit of `numerator / denominator` as `x -> at`, repeatedly applying
+/// L'Hôpital's Rule while the quotient stays in the `0/0` or `∞/∞` form.
+///
+/// `at` may be an `f64` or a [`LimitPoint`]; limits at infinity are evaluated by
+/// sampling at [`INFINITY_PROXY`], with a quotient that keeps decaying or growing
+/// further out, like `ln(x) / x`, taken to be exactly 0 or infinite, and a NaN point
+/// gives [`LhopitalError::InvalidLimitPoint`]. A nonzero numerator over a vanishing
+/// denominator gives a signed infinity when the sign agrees on every side the point
+/// is approached from, and [`LhopitalError::InfiniteLimit`] otherwise. A quotient
+/// that is undefined at the point itself is sampled just beside it instead, and if the
+/// two sides of a two-sided limit disagree, as for `x / |x|` at 0, the result is
+/// [`LhopitalError::LimitDoesNotExist`] with both values. A quotient
+/// involving an [`Expression::Piecewise`] is solved on each side separately, with the
+/// branches holding just beside the point. A NaN or infinite [`Expression::Constant`]
+/// in either part gives [`LhopitalError::DomainError`].
+///
+/// Parts that both vanish at a finite point are first simplified, and if they are
+/// polynomials then, have their common factor `x - at` divided out with
+/// [`try_factor_cancel`], so a removable discontinuity like `(x^2 - 4) / (x - 2)` at 2,
+/// or `(x^2 - 4 + 0 * sin(x)) / (x - 2)`, is settled exactly without differentiating. A quotient of
+/// polynomials is differentiated at most one more time than the higher
+/// [`Expression::degree`] of the two, however large `max_iterations` is, and one whose
+/// parts both simplify to zero gives [`LhopitalError::Undefined`] straight away. Once
+/// both parts are the same function up to the order of operands, and nonzero beside
+/// the point, or at the infinity proxy even if they overflow there, the limit is 1
+/// without differentiating further.
+///
+/// The parts may be of any [`Differentiable`] type. Every step above that looks at the
+/// structure of the parts, from splitting piecewise expressions to comparing them up
+/// to the order of operands, applies to [`Expression`] alone, while other types have
+/// the rule applied to them as they stand, with the same tolerances, trace and guards.
+pub fn lhopital_solve<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+    max_iterations: u32,
+) -> Result<f64, LhopitalError> {
+    lhopital_solve_traced(numerator, denominator, at, max_iterations).map(|result| result.value)
+}
+
+/// Like [`lhopital_solve`], applying the rule at most [`DEFAULT_MAX_ITERATIONS`] times.
+pub fn lhopital_solve_default<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+) -> Result<f64, LhopitalError> {
+    lhopital_solve(numerator, denominator, at, DEFAULT_MAX_ITERATIONS)
+}
+
+/// The limit of `numerator / denominator` as `x -> at`, or `None` if it cannot be
+/// found for any reason. This is [`lhopital_solve_default`] without the error, for
+/// when only the value matters.
+pub fn limit<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+) -> Option<f64> {
+    lhopital_solve_default(numerator, denominator, at).ok()
+}
+
+// With a NaN or infinite constant in the quotient the form checks end up comparing
+// infinities with infinities, and can read off a limit of the wrong size.
+fn check_finite_constants(expr: &Expression) -> Result<(), LhopitalError> {
+    match expr
+        .iter_nodes()
+        .find(|node| matches!(node, Expression::Constant(c) if !c.is_finite()))
+    {
+        Some(node) => Err(LhopitalError::DomainError(node.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Solves every `(numerator, denominator, at)` problem with [`lhopital_solve_default`],
+/// returning the results in the same order.
+///
+/// With the `rayon` feature the problems are solved in parallel. Expressions share
+/// their nodes through `Rc`, so they cannot cross threads themselves; instead each
+/// problem is written out with [`Expression::to_sexpr`] and read back on the thread
+/// that solves it. A part that does not read back gives
+/// [`LhopitalError::BatchTransfer`] for its problem.
+pub fn solve_batch(problems: &[(Expression, Expression, f64)]) -> Vec<Result<f64, LhopitalError>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+
+        let written: Vec<_> = problems
+            .iter()
+            .map(|(num, den, at)| (num.to_sexpr(), den.to_sexpr(), *at))
+            .collect();
+        written
+            .par_iter()
+            .map(|(num, den, at)| {
+                let read = |sexpr: &String| {
+                    Expression::from_sexpr(sexpr)
+                        .map_err(|_| LhopitalError::BatchTransfer(sexpr.clone()))
+                };
+                lhopital_solve_default(&read(num)?, &read(den)?, *at)
+            })
+            .collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    problems
+        .iter()
+        .map(|(num, den, at)| lhopital_solve_default(num, den, *at))
+        .collect()
+}
+
+/// Like [`lhopital_solve`], but treats values with magnitude below `tolerance` as zero
+/// instead of using [`DEFAULT_TOLERANCE`].
+pub fn lhopital_solve_with_tolerance<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+    max_iterations: u32,
+    tolerance: f64,
+) -> Result<f64, LhopitalError> {
+    let config = SolveConfig::default()
+        .max_iterations(max_iterations)
+        .tolerance(tolerance);
+    solve(
+        numerator,
+        denominator,
+        at.into(),
+        &config,
+        &mut io::stdout(),
+    )
+    .map(|result| result.value)
+}
+
+/// Like [`lhopital_solve`], but returns every step the solver took alongside the limit.
+pub fn lhopital_solve_traced<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+    max_iterations: u32,
+) -> Result<LhopitalResult<T>, LhopitalError> {
+    let config = SolveConfig::default().max_iterations(max_iterations);
+    solve(
+        numerator,
+        denominator,
+        at.into(),
+        &config,
+        &mut io::stdout(),
+    )
+}
+
+/// Like [`lhopital_solve_traced`], with every setting taken from `config`.
+pub fn lhopital_solve_with<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+    config: &SolveConfig,
+) -> Result<LhopitalResult<T>, LhopitalError> {
+    solve_with_trace_to_writer(numerator, denominator, at, config, &mut io::stdout())
+}
+
+/// Like [`lhopital_solve_with`], but writes the step-by-step output enabled by
+/// [`SolveConfig::verbose`] to `out` instead of standard output, e.g. to capture it in
+/// a file or a buffer. Failed writes are ignored, since the trace never changes the
+/// result.
+pub fn solve_with_trace_to_writer<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+    config: &SolveConfig,
+    out: &mut dyn Write,
+) -> Result<LhopitalResult<T>, LhopitalError> {
+    solve(numerator, denominator, at.into(), config, out)
+}
+
+/// How many applications of L'Hôpital's Rule [`lhopital_solve`] needs before the
+/// quotient becomes determinate, e.g. 2 for `(1 - cos(x)) / x^2` at 0. A quotient
+/// that is determinate already needs 0.
+pub fn lhopital_order<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: impl Into<LimitPoint>,
+    max_iterations: u32,
+) -> Result<usize, LhopitalError> {
+    let config = SolveConfig::default()
+        .max_iterations(max_iterations)
+        .numeric_check(false)
+        .cancel_factors(false);
+    solve(
+        numerator,
+        denominator,
+        at.into(),
+        &config,
+        &mut io::stdout(),
+    )
+    .map(|result| result.iterations_applied as usize)
+}
+
+/// The outcome of a single application of L'Hôpital's Rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LhopitalStep<T = Expression> {
+    /// The quotient was indeterminate, so the rule was applied; its limit is the limit
+    /// of this quotient of derivatives.
+    Differentiated { numerator: T, denominator: T },
+    /// The quotient was determinate with this value.
+    Value(f64),
+}
+
+/// Applies L'Hôpital's Rule once: if `numerator / denominator` is in the `0/0` or
+/// `∞/∞` form at `at`, returns the simplified quotient of their derivatives, and
+/// otherwise its value.
+///
+/// Unlike [`lhopital_solve`] this does not sample beside the point, so an
+/// expression that cannot be evaluated at `at` gives [`LhopitalError::Undefined`].
+pub fn apply_lhopital_once(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: impl Into<LimitPoint>,
+) -> Result<LhopitalStep, LhopitalError> {
+    let at = at.into();
+    let x = at.sample_point();
+    let num_val = numerator.evaluate(x);
+    let den_val = denominator.evaluate(x);
+    if num_val.is_nan() || den_val.is_nan() {
+        return Err(LhopitalError::Undefined);
+    }
+    classify(
+        numerator,
+        denominator,
+        (num_val, den_val),
+        at,
+        &SolveConfig::default(),
+    )
+}
+
+/// Checks that `numerator / denominator` is in the `0/0` or `∞/∞` form at `at`, the
+/// only forms L'Hôpital's Rule applies to.
+///
+/// A determinate quotient gives [`LhopitalError::Determinate`] with its value, and a
+/// nonzero numerator over a vanishing denominator gives
+/// [`LhopitalError::DivisionByZero`].
+pub fn assert_indeterminate(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: impl Into<LimitPoint>,
+) -> Result<(), LhopitalError> {
+    let x = at.into().sample_point();
+    let num_val = numerator.evaluate(x);
+    let den_val = denominator.evaluate(x);
+    if num_val.is_nan() || den_val.is_nan() {
+        Err(LhopitalError::Undefined)
+    } else if is_indeterminate(num_val, den_val, DEFAULT_TOLERANCE) {
+        Ok(())
+    } else if den_val.abs() < DEFAULT_TOLERANCE {
+        Err(LhopitalError::DivisionByZero)
+    } else {
+        Err(LhopitalError::Determinate(num_val / den_val))
+    }
+}
+
+/// Checks the preconditions of L'Hôpital's Rule for `numerator / denominator` at
+/// `at`: the quotient must be in an indeterminate form, as for
+/// [`assert_indeterminate`], both parts must be differentiable, and the derivative of
+/// the denominator must not vanish beside the point.
+///
+/// A denominator whose derivative vanishes only at the point itself, like `x^2` at 0,
+/// passes, since the rule can be applied again. One whose derivative also vanishes
+/// just beside it gives [`LhopitalError::DivisionByZero`].
+pub fn lhopital_applicable(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: impl Into<LimitPoint>,
+) -> Result<(), LhopitalError> {
+    let at = at.into().validated()?;
+    assert_indeterminate(numerator, denominator, at)?;
+    numerator.differentiate()?;
+    let den_derivative = denominator.differentiate()?;
+
+    if points_beside(at, NUMERIC_EPSILON)
+        .into_iter()
+        .any(|x| den_derivative.evaluate(x) == 0.0)
+    {
+        Err(LhopitalError::DivisionByZero)
+    } else {
+        Ok(())
+    }
+}
+
+/// The form of a quotient at a limit point by which of its parts vanish there, as
+/// reported by [`probe`]. `c` stands for any other value, finite or not, except where
+/// both parts are unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbedForm {
+    ZeroOverZero,
+    InfOverInf,
+    /// A nonzero numerator over a vanishing denominator.
+    ConstantOverZero,
+    /// A vanishing numerator over a nonzero denominator.
+    ZeroOverConstant,
+    /// Neither part vanishes.
+    ConstantOverConstant,
+    /// A part cannot be evaluated at the point.
+    Undefined,
+}
+
+impl fmt::Display for ProbedForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbedForm::ZeroOverZero => write!(f, "0/0"),
+            ProbedForm::InfOverInf => write!(f, "∞/∞"),
+            ProbedForm::ConstantOverZero => write!(f, "c/0"),
+            ProbedForm::ZeroOverConstant => write!(f, "0/c"),
+            ProbedForm::ConstantOverConstant => write!(f, "c/c"),
+            ProbedForm::Undefined => write!(f, "undefined"),
+        }
+    }
+}
+
+/// The outcome of [`probe`]: the form of the quotient and the values it was read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FormReport {
+    pub form: ProbedForm,
+    pub numerator_value: f64,
+    pub denominator_value: f64,
+}
+
+/// Evaluates `numerator` and `denominator` once at `at` and reports their form there,
+/// without differentiating or solving anything.
+///
+/// Values with magnitude below [`DEFAULT_TOLERANCE`] count as zero, and values at
+/// least as large as [`INFINITY_PROXY`] as unbounded.
+pub fn probe(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: impl Into<LimitPoint>,
+) -> FormReport {
+    let x = at.into().sample_point();
+    let num_val = numerator.evaluate(x);
+    let den_val = denominator.evaluate(x);
+    let is_zero = |value: f64| value.abs() < DEFAULT_TOLERANCE;
+    let form = if num_val.is_nan() || den_val.is_nan() {
+        ProbedForm::Undefined
+    } else if is_unbounded(num_val) && is_unbounded(den_val) {
+        ProbedForm::InfOverInf
+    } else {
+        match (is_zero(num_val), is_zero(den_val)) {
+            (true, true) => ProbedForm::ZeroOverZero,
+            (false, true) => ProbedForm::ConstantOverZero,
+            (true, false) => ProbedForm::ZeroOverConstant,
+            (false, false) => ProbedForm::ConstantOverConstant,
+        }
+    };
+    FormReport {
+        form,
+        numerator_value: num_val,
+        denominator_value: den_val,
+    }
+}
+
+/// The form of a quotient at a limit point, as reported by [`classify_form`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotientForm {
+    /// A `0/0` form of polynomials whose common root at the point is at least as
+    /// repeated in the numerator, so the limit is finite once it is cancelled.
+    Removable,
+    /// A nonzero numerator over a vanishing denominator, or a `0/0` form of
+    /// polynomials whose root at the point is more repeated in the denominator.
+    Pole,
+    /// The quotient can be evaluated at the point directly.
+    Determinate,
+    /// A `0/0` form that is not known to be removable.
+    Indeterminate0_0,
+    /// Both the numerator and the denominator are unbounded.
+    IndeterminateInfInf,
+}
+
+/// Classifies `numerator / denominator` at `at` without iterating, so callers can
+/// decide upfront whether to apply L'Hôpital's Rule.
+///
+/// Only quotients of polynomials are recognised as [`QuotientForm::Removable`]: the
+/// multiplicity of the root is read off from how many derivatives vanish at the
+/// point. An expression that cannot be evaluated at `at` gives
+/// [`LhopitalError::Undefined`], and a denominator that vanishes identically gives
+/// [`LhopitalError::DivisionByZero`].
+pub fn classify_form(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: impl Into<LimitPoint>,
+) -> Result<QuotientForm, LhopitalError> {
+    let x = at.into().sample_point();
+    let num_val = numerator.evaluate(x);
+    let den_val = denominator.evaluate(x);
+    if num_val.is_nan() || den_val.is_nan() {
+        return Err(LhopitalError::Undefined);
+    }
+    if both_unbounded(num_val, den_val) {
+        return Ok(QuotientForm::IndeterminateInfInf);
+    }
+    if den_val.abs() >= DEFAULT_TOLERANCE {
+        return Ok(QuotientForm::Determinate);
+    }
+    if num_val.abs() >= DEFAULT_TOLERANCE {
+        return Ok(QuotientForm::Pole);
+    }
+    if !is_polynomial(numerator) || !is_polynomial(denominator) {
+        return Ok(QuotientForm::Indeterminate0_0);
+    }
+    let den_order = root_order(denominator, x)?.ok_or(LhopitalError::DivisionByZero)?;
+    match root_order(numerator, x)? {
+        Some(num_order) if num_order < den_order => Ok(QuotientForm::Pole),
+        _ => Ok(QuotientForm::Removable),
+    }
+}
+
+/// Divides the common factor `x - at` out of two polynomials that both vanish at `at`,
+/// returning the reduced `(numerator, denominator)`. For `(x^2 - 4) / (x - 2)` at 2
+/// this gives `(x + 2, 1)`.
+///
+/// Returns `None` unless both are polynomials that [`classify_form`] would accept,
+/// both vanish at `at`, and the denominator is not identically zero.
+pub fn try_factor_cancel(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: f64,
+) -> Option<(Expression, Expression)> {
+    // Synthetic division by `x - at`, giving the quotient and the remainder `p(at)`.
+    let divide = |coefficients: Vec<f64>| {
+        let error = polynomial_rounding_error(&coefficients, at);
+        let (&constant, rest) = coefficients.split_first()?;
+        let mut quotient = vec![0.0; rest.len()];
+        let mut carry = 0.0;
+        for (k, &coefficient) in rest.iter().enumerate().rev() {
+            carry = coefficient + at * carry;
+            quotient[k] = carry;
+        }
+        let remainder = constant + at * carry;
+        (remainder.abs() < DEFAULT_TOLERANCE.max(error)).then_some(quotient)
+    };
+    if !is_polynomial(numerator) || !is_polynomial(denominator) {
+        return None;
+    }
+    let den = coefficients(denominator)?;
+    if den.iter().all(|&c| c == 0.0) {
+        return None;
+    }
+    let num = divide(coefficients(numerator)?)?;
+    let den = divide(den)?;
+    Some((
+        Expression::from_polynomial(&num),
+        Expression::from_polynomial(&den),
+    ))
+}
+
+// Powers above this degree are not expanded into coefficients.
+const MAX_DEGREE: usize = 1_000;
+
+// The coefficients of a polynomial accepted by `is_polynomial`, lowest degree first and
+// without trailing zeros.
+fn coefficients(expression: &Expression) -> Option<Vec<f64>> {
+    fn trim(mut coefficients: Vec<f64>) -> Vec<f64> {
+        while coefficients.last() == Some(&0.0) {
+            coefficients.pop();
+        }
+        coefficients
+    }
+    fn combine(a: &[f64], b: &[f64], op: impl Fn(f64, f64) -> f64) -> Vec<f64> {
+        let len = a.len().max(b.len());
+        let at = |c: &[f64], k: usize| c.get(k).copied().unwrap_or(0.0);
+        trim((0..len).map(|k| op(at(a, k), at(b, k))).collect())
+    }
+    fn multiply(a: &[f64], b: &[f64]) -> Vec<f64> {
+        if a.is_empty() || b.is_empty() {
+            return Vec::new();
+        }
+        let mut product = vec![0.0; a.len() + b.len() - 1];
+        for (i, &x) in a.iter().enumerate() {
+            for (j, &y) in b.iter().enumerate() {
+                product[i + j] += x * y;
+            }
+        }
+        trim(product)
+    }
+
+    let coefficients = match expression {
+        Expression::Variable => vec![0.0, 1.0],
+        Expression::Sum(a, b) => combine(&coefficients(a)?, &coefficients(b)?, |x, y| x + y),
+        Expression::Difference(a, b) => combine(&coefficients(a)?, &coefficients(b)?, |x, y| x - y),
+        Expression::Negate(a) => coefficients(a)?.iter().map(|c| -c).collect(),
+        Expression::Product(a, b) => multiply(&coefficients(a)?, &coefficients(b)?),
+        Expression::Quotient(a, b) => {
+            let divisor = b.constant_value().filter(|&d| d != 0.0)?;
+            coefficients(a)?.iter().map(|c| c / divisor).collect()
+        }
+        Expression::Power(base, n) => {
+            let base = coefficients(base)?;
+            if base.len().saturating_sub(1) * (*n as usize) > MAX_DEGREE {
+                return None;
+            }
+            (0..*n as usize).fold(vec![1.0], |power, _| multiply(&power, &base))
+        }
+        constant => vec![constant.constant_value()?],
+    };
+    Some(trim(coefficients))
+}
+
+// Whether the expression is a polynomial in `x`, built only from constants, `x`,
+// sums, products, non-negative integer powers and division by constants.
+fn is_polynomial(expression: &Expression) -> bool {
+    match expression {
+        Expression::Constant(_) | Expression::Pi | Expression::E | Expression::Variable => true,
+        Expression::Sum(a, b) | Expression::Difference(a, b) | Expression::Product(a, b) => {
+            is_polynomial(a) && is_polynomial(b)
+        }
+        Expression::Negate(a) => is_polynomial(a),
+        #[cfg(feature = "rational")]
+        Expression::Rational(..) => true,
+        Expression::Quotient(a, b) => is_polynomial(a) && b.is_constant(),
+        Expression::Power(base, n) => *n >= 0.0 && n.fract() == 0.0 && is_polynomial(base),
+        _ => false,
+    }
+}
+
+// The multiplicity of `x` as a root of a polynomial: the number of its successive
+// derivatives, starting from itself, that vanish there. `None` means every
+// derivative vanishes, so the polynomial is identically zero.
+fn root_order(polynomial: &Expression, x: f64) -> Result<Option<usize>, LhopitalError> {
+    let mut current = polynomial.clone();
+    let mut order = 0;
+    // A polynomial is zero after one more derivative than its degree.
+    let degree = polynomial.degree().unwrap_or(MAX_DEGREE);
+    while order <= degree {
+        if current.evaluate(x).abs() >= DEFAULT_TOLERANCE {
+            return Ok(Some(order));
+        }
+        current = current.differentiate()?.simplify();
+        order += 1;
+    }
+    Ok(None)
+}
+
+fn detected_form(num_val: f64, den_val: f64, tolerance: f64) -> Option<DetectedForm> {
+    if num_val.abs() < tolerance && den_val.abs() < tolerance {
+        Some(DetectedForm::ZeroOverZero)
+    } else if is_unbounded(num_val) && is_unbounded(den_val) {
+        Some(DetectedForm::InfOverInf)
+    } else {
+        None
+    }
+}
+
+fn is_indeterminate(num_val: f64, den_val: f64, tolerance: f64) -> bool {
+    (num_val.abs() < tolerance && den_val.abs() < tolerance) || both_unbounded(num_val, den_val)
+}
+
+// Decides from the values of `numerator` and `denominator` at the limit point whether
+// the quotient is determinate, differentiating both if it is not.
+fn classify<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    (num_val, den_val): (f64, f64),
+    at: LimitPoint,
+    config: &SolveConfig,
+) -> Result<LhopitalStep<T>, LhopitalError> {
+    if is_indeterminate(num_val, den_val, config.tolerance) {
+        let derivatives = numerator
+            .differentiate()
+            .and_then(|num| Ok((num, denominator.differentiate()?)));
+        match derivatives {
+            Ok((numerator, denominator)) => Ok(LhopitalStep::Differentiated {
+                numerator,
+                denominator,
+            }),
+            Err(LhopitalError::DifferentiationUnsupported(_)) if config.numeric_derivatives => {
+                numeric_lhopital(numerator, denominator, at, config.epsilon)
+                    .map(LhopitalStep::Value)
+            }
+            Err(error) => Err(error),
+        }
+    } else if den_val.abs() < config.tolerance {
+        infinite_limit(num_val, denominator, at, config.epsilon).map(LhopitalStep::Value)
+    } else {
+        Ok(LhopitalStep::Value(quotient_at(
+            numerator,
+            denominator,
+            (num_val, den_val),
+            at,
+        )))
+    }
+}
+
+// Estimates the limit of `f'/g'` beside the point from central differences, for
+// quotients without a symbolic derivative. The step shrinks well below `epsilon` so
+// that it never reaches the point itself, and grows with `x` so that it stays
+// representable near the infinity proxy.
+fn numeric_lhopital<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: LimitPoint,
+    epsilon: f64,
+) -> Result<f64, LhopitalError> {
+    let value = sample_beside(at, epsilon, |x| {
+        let h = epsilon * x.abs().max(1.0) / 10.0;
+        central_difference(numerator, x, h) / central_difference(denominator, x, h)
+    })?;
+    if value.is_nan() {
+        Err(LhopitalError::Undefined)
+    } else {
+        Ok(value)
+    }
+}
+
+// `(f(x + h) - f(x - h)) / 2h`, as `Expression::numeric_derivative` gives for any type.
+fn central_difference<T: Differentiable>(f: &T, x: f64, h: f64) -> f64 {
+    (f.evaluate(x + h) - f.evaluate(x - h)) / (2.0 * h)
+}
+
+// A nonzero numerator over a vanishing denominator tends to an infinity, signed by the
+// quotient just beside the point on the side(s) it is approached from; opposite signs
+// on the two sides are reported with both infinities. A denominator that also vanishes
+// there is a genuine division by zero. At an infinite point there is nothing beside the
+// infinity proxy, so the sign comes from the denominator there, which may have
+// underflowed to a signed zero, as `exp(x)` does at `-∞`.
+fn infinite_limit<T: Differentiable>(
+    num_val: f64,
+    denominator: &T,
+    at: LimitPoint,
+    epsilon: f64,
+) -> Result<f64, LhopitalError> {
+    let signed_infinity = |x: f64| {
+        let den_val = denominator.evaluate(x);
+        if den_val == 0.0 || den_val.is_nan() {
+            Err(LhopitalError::DivisionByZero)
+        } else {
+            Ok(f64::INFINITY.copysign(num_val * den_val))
+        }
+    };
+    let x = at.sample_point();
+    if let LimitPoint::PosInfinity | LimitPoint::NegInfinity = at {
+        let den_val = denominator.evaluate(x);
+        return if den_val.is_nan() {
+            Err(LhopitalError::DivisionByZero)
+        } else {
+            Ok(f64::INFINITY.copysign(num_val) * den_val.signum())
+        };
+    }
+    match at.direction() {
+        Direction::Left => signed_infinity(x - epsilon),
+        Direction::Right => signed_infinity(x + epsilon),
+        Direction::Both => {
+            let left = signed_infinity(x - epsilon)?;
+            let right = signed_infinity(x + epsilon)?;
+            if left == right {
+                Ok(left)
+            } else {
+                Err(LhopitalError::InfiniteLimit { left, right })
+            }
+        }
+    }
+}
+
+// Whether the value of `expression` at the sample point for `at` is past `max_magnitude`.
+// At a finite point an infinity is a singularity rather than an overflow.
+fn overflows<T: Differentiable>(expression: &T, at: LimitPoint, max_magnitude: f64) -> bool {
+    let value = expression.evaluate(at.sample_point());
+    let infinite_point = matches!(at, LimitPoint::PosInfinity | LimitPoint::NegInfinity);
+    value.abs() > max_magnitude && (value.is_finite() || infinite_point)
+}
+
+// The value of `expression` at the sample point for `at`, except that at an infinite
+// point a term found to grow without bound, like `ln(x)`, is an actual infinity. That
+// lets `∞/∞` be detected for terms that stay below the infinity proxy. A decaying term
+// keeps its sampled value, since reading it as exactly zero would turn determinate
+// quotients of small values into `0/0`.
+fn value_at<T: Differentiable>(expression: &T, at: LimitPoint) -> f64 {
+    let x = at.sample_point();
+    let sign = match at {
+        LimitPoint::PosInfinity => 1.0,
+        LimitPoint::NegInfinity => -1.0,
+        _ => return expression.evaluate(x),
+    };
+    let trend = trend_at_infinity(|x| expression.evaluate(x), sign, INFINITY_PROXY);
+    if trend.is_infinite() {
+        trend
+    } else {
+        expression.evaluate(x)
+    }
+}
+
+// The determinate quotient of the values `num_val / den_val` at the limit point, except
+// that at an infinite point a quotient found to decay or grow without bound is exactly
+// zero or infinite. Dividing the sampled parts alone would read `ln(x) / x` at the
+// infinity proxy as `1e-8`, however near to zero it really tends.
+fn quotient_at<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    (num_val, den_val): (f64, f64),
+    at: LimitPoint,
+) -> f64 {
+    let sign = match at {
+        LimitPoint::PosInfinity => 1.0,
+        LimitPoint::NegInfinity => -1.0,
+        _ => return num_val / den_val,
+    };
+    let trend = trend_at_infinity(
+        |x| numerator.evaluate(x) / denominator.evaluate(x),
+        sign,
+        INFINITY_PROXY,
+    );
+    if trend == 0.0 || trend.is_infinite() {
+        trend
+    } else {
+        num_val / den_val
+    }
+}
+
+// Zero if `value`, the value of `expression` at a finite `at`, is only rounding error:
+// within what evaluating a polynomial there can be off by, or below the relative
+// tolerance compared with the expression's magnitude `epsilon` to either side.
+// Otherwise `value` itself.
+fn snapped_to_zero<T: Differentiable>(
+    value: f64,
+    expression: &T,
+    at: LimitPoint,
+    config: &SolveConfig,
+    refinements: &Refinements<T>,
+) -> f64 {
+    if matches!(at, LimitPoint::PosInfinity | LimitPoint::NegInfinity) {
+        return value;
+    }
+    let x = at.sample_point();
+    if (refinements.rounding_error)(expression, x).is_some_and(|error| value.abs() <= error) {
+        return 0.0;
+    }
+    if config.relative_tolerance == 0.0 {
+        return value;
+    }
+    let scale = expression
+        .evaluate(x - config.epsilon)
+        .abs()
+        .max(expression.evaluate(x + config.epsilon).abs());
+    if scale.is_finite() && value.abs() < config.relative_tolerance * scale {
+        0.0
+    } else {
+        value
+    }
+}
+
+// A bound on the rounding error in evaluating `expression` at `x`, if it is a
+// polynomial. Each term carries an error of a few ulps of its own magnitude, so a root
+// of a polynomial with large terms can evaluate well above the tolerance, as
+// `x^3 - 3000.6x + ...` does at its double root near 1000.
+fn rounding_error(expression: &Expression, x: f64) -> Option<f64> {
+    if !is_polynomial(expression) {
+        return None;
+    }
+    let coefficients = coefficients(expression)?;
+    Some(polynomial_rounding_error(&coefficients, x))
+}
+
+// A bound on the rounding error in evaluating the polynomial with `coefficients`,
+// lowest degree first, at `x`.
+fn polynomial_rounding_error(coefficients: &[f64], x: f64) -> f64 {
+    let magnitude = coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |sum, c| sum * x.abs() + c.abs());
+    (coefficients.len() + 2) as f64 * f64::EPSILON * magnitude
+}
+
+// A value printed with `precision` decimal places, or in full without one.
+fn format_value(value: f64, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => format!("{:.*}", precision, value),
+        None => value.to_string(),
+    }
+}
+
+// Validates the point and solves the quotient the way its type does, which for every
+// type but `Expression` is applying the rule as it stands.
+fn solve<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: LimitPoint,
+    config: &SolveConfig,
+    out: &mut dyn Write,
+) -> Result<LhopitalResult<T>, LhopitalError> {
+    T::solve_limit(numerator, denominator, at.validated()?, config, out)
+}
+
+// The parts of applying the rule that need the structure of an `Expression`. Other
+// types go without them.
+struct Refinements<T> {
+    // A bound on the rounding error of evaluating a part at `x`, within which its value
+    // counts as zero.
+    rounding_error: fn(&T, f64) -> Option<f64>,
+    // Whether the two parts are the same function, making the limit 1.
+    same_function: fn(&T, &T) -> bool,
+    // The parts rearranged into a quotient that may be defined where theirs is not.
+    cross_multiplied: fn(&T, &T) -> Option<(T, T)>,
+    // Rejects a derivative nested more deeply than the configured limit.
+    check_depth: fn(&T, usize) -> Result<(), LhopitalError>,
+}
+
+impl<T: Differentiable> Refinements<T> {
+    fn none() -> Self {
+        Refinements {
+            rounding_error: |_, _| None,
+            same_function: |a, b| a == b,
+            cross_multiplied: |_, _| None,
+            check_depth: |_, _| Ok(()),
+        }
+    }
+}
+
+const EXPRESSION_REFINEMENTS: Refinements<Expression> = Refinements {
+    rounding_error,
+    same_function,
+    cross_multiplied,
+    check_depth: Expression::check_depth,
+};
+
+// A quotient ready for the rule to be applied to, with what was done to get it there.
+struct Prepared<'a, T> {
+    // The quotient as given, which sampling beside the point checks the domain of.
+    original: (&'a T, &'a T),
+    numerator: T,
+    denominator: T,
+    // Steps already taken, like cancelling a common factor.
+    iterations: Vec<Step<T>>,
+    // The form of the original quotient, if it was rewritten into this one.
+    rewritten_from: Option<DetectedForm>,
+    max_iterations: u32,
+}
+
+// Solves a quotient of any type but `Expression` by applying the rule as it stands.
+pub(crate) fn solve_as_given<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: LimitPoint,
+    config: &SolveConfig,
+    out: &mut dyn Write,
+) -> Result<LhopitalResult<T>, LhopitalError> {
+    let prepared = Prepared {
+        original: (numerator, denominator),
+        numerator: numerator.clone(),
+        denominator: denominator.clone(),
+        iterations: Vec::new(),
+        rewritten_from: None,
+        max_iterations: config.max_iterations,
+    };
+    apply_rule(prepared, at, config, out, &Refinements::none())
+}
+
+// Solves a quotient of expressions, first reworking it where its structure allows:
+// piecewise parts are split, a constant denominator is divided out, the `0·∞` and
+// `∞-∞` forms are rewritten as quotients and common polynomial factors are cancelled.
+pub(crate) fn solve_expression(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: LimitPoint,
+    config: &SolveConfig,
+    out: &mut dyn Write,
+) -> Result<LhopitalResult, LhopitalError> {
+    numerator.check_depth(config.max_depth)?;
+    denominator.check_depth(config.max_depth)?;
+    check_finite_constants(numerator)?;
+    check_finite_constants(denominator)?;
+    let is_piecewise = |e: &Expression| {
+        e.iter_nodes()
+            .any(|node| matches!(node, Expression::Piecewise(_)))
+    };
+    if is_piecewise(numerator) || is_piecewise(denominator) {
+        return solve_piecewise(numerator, denominator, at, config, out);
+    }
+    // Both parts vanishing identically stay `0/0` through every derivative.
+    if denominator.simplify().is_zero() && numerator.simplify().is_zero() {
+        return Err(LhopitalError::Undefined);
+    }
+    let x = at.sample_point();
+    let tolerance = config.tolerance;
+
+    // Over a nonzero constant denominator the limit is just the numerator's value.
+    if let Some(den_val) = denominator.constant_value() {
+        let num_val = value_at(numerator, at);
+        if num_val.is_finite() && den_val.is_finite() && den_val.abs() >= tolerance {
+            return Ok(LhopitalResult {
+                value: quotient_at(numerator, denominator, (num_val, den_val), at),
+                iterations: vec![Step {
+                    numerator: numerator.clone(),
+                    denominator: denominator.clone(),
+                    numerator_value: num_val,
+                    denominator_value: den_val,
+                    form: None,
+                }],
+                iterations_applied: 0,
+                final_numerator: numerator.clone(),
+                final_denominator: denominator.clone(),
+                numeric_mismatch: None,
+            });
+        }
+    }
+
+    // A numerator in the 0·∞ or ∞-∞ form over a well-behaved denominator is rewritten
+    // into a quotient first, since L'Hôpital's Rule only applies to quotients.
+    let den_val = denominator.evaluate(x);
+    let rewritten = if den_val.is_finite() && den_val.abs() >= tolerance {
+        to_quotient_form(numerator, at)
+    } else {
+        None
+    };
+    let mut rewritten_from = rewritten.as_ref().map(|_| match numerator {
+        Expression::Product(..) => DetectedForm::ZeroTimesInf,
+        _ => DetectedForm::InfMinusInf,
+    });
+    let (mut num, mut den) = match rewritten {
+        Some((num, factor)) => {
+            let den = Expression::Product(Rc::new(denominator.clone()), Rc::new(factor));
+            (num.simplify(), den.simplify())
+        }
+        None => (numerator.clone(), denominator.clone()),
+    };
+    let mut iterations = Vec::new();
+
+    // Polynomials sharing the root `at` have the factor `x - at` divided out for as
+    // long as both vanish there, which settles the limit without differentiating. They
+    // are simplified first, which uncovers polynomials behind identities like
+    // `0 * sin(x)`; since `simplify` keeps every factor that may be undefined, what it
+    // uncovers is defined wherever the given quotient is.
+    let finite = !matches!(at, LimitPoint::PosInfinity | LimitPoint::NegInfinity);
+    if config.cancel_factors
+        && finite
+        && let Some(mut cancelled) = try_factor_cancel(&num.simplify(), &den.simplify(), x)
+    {
+        iterations.push(Step {
+            numerator: num.clone(),
+            denominator: den.clone(),
+            numerator_value: num.evaluate(x),
+            denominator_value: den.evaluate(x),
+            form: rewritten_from.take().or(Some(DetectedForm::ZeroOverZero)),
+        });
+        while let Some(further) = try_factor_cancel(&cancelled.0, &cancelled.1, x) {
+            cancelled = further;
+        }
+        if config.verbose {
+            let _ = writeln!(out, "Cancelled x - {} from ({}) / ({})", x, num, den);
+        }
+        (num, den) = cancelled;
+    }
+
+    // Polynomials reach a determinate form after at most as many derivatives as their
+    // degree, so a quotient of them that needs more will never get there.
+    let max_iterations = match (num.degree(), den.degree()) {
+        (Some(num_degree), Some(den_degree)) => {
+            let needed = u32::try_from(num_degree.max(den_degree) + 1).unwrap_or(u32::MAX);
+            config.max_iterations.min(needed)
+        }
+        _ => config.max_iterations,
+    };
+    let prepared = Prepared {
+        original: (numerator, denominator),
+        numerator: num,
+        denominator: den,
+        iterations,
+        rewritten_from,
+        max_iterations,
+    };
+    apply_rule(prepared, at, config, out, &EXPRESSION_REFINEMENTS)
+}
+
+// Applies L'Hôpital's Rule for as long as the quotient stays indeterminate, reading off
+// the limit once it is not.
+fn apply_rule<T: Differentiable>(
+    prepared: Prepared<T>,
+    at: LimitPoint,
+    config: &SolveConfig,
+    out: &mut dyn Write,
+    refinements: &Refinements<T>,
+) -> Result<LhopitalResult<T>, LhopitalError> {
+    let Prepared {
+        original: (numerator, denominator),
+        numerator: mut num,
+        denominator: mut den,
+        mut iterations,
+        mut rewritten_from,
+        max_iterations,
+    } = prepared;
+    let x = at.sample_point();
+    let tolerance = config.tolerance;
+    let mut iterations_applied = 0;
+
+    // Each pass evaluates the quotient and differentiates it if still indeterminate,
+    // so one more pass than `max_iterations` evaluates the last derivatives too.
+    for i in 0..=max_iterations {
+        let num_val = snapped_to_zero(value_at(&num, at), &num, at, config, refinements);
+        let den_val = snapped_to_zero(value_at(&den, at), &den, at, config, refinements);
+        if config.verbose {
+            let _ = writeln!(
+                out,
+                "Iteration {}: ({}) / ({}) at x = {}: {} / {}",
+                i,
+                num,
+                den,
+                at,
+                format_value(num_val, config.precision),
+                format_value(den_val, config.precision)
+            );
+        }
+        iterations.push(Step {
+            numerator: num.clone(),
+            denominator: den.clone(),
+            numerator_value: num_val,
+            denominator_value: den_val,
+            form: rewritten_from
+                .take()
+                .or_else(|| detected_form(num_val, den_val, tolerance)),
+        });
+
+        // `f / f` is 1 wherever `f` is defined and nonzero, so once both parts are the
+        // same such function beside the point there is nothing left to differentiate.
+        // At an infinite point there is only the infinity proxy, where `exp(x)` has
+        // overflowed, so there the parts need only be nonzero, and this comes before the
+        // overflow guard.
+        let infinite_point = matches!(at, LimitPoint::PosInfinity | LimitPoint::NegInfinity);
+        if (refinements.same_function)(&num, &den)
+            && points_beside(at, config.epsilon).into_iter().all(|x| {
+                let value = num.evaluate(x);
+                if infinite_point {
+                    !value.is_nan() && value != 0.0
+                } else {
+                    value.is_finite()
+                        && value != 0.0
+                        && !(numerator.evaluate(x) / denominator.evaluate(x)).is_nan()
+                }
+            })
+        {
+            return Ok(LhopitalResult {
+                value: 1.0,
+                iterations,
+                iterations_applied,
+                final_numerator: num,
+                final_denominator: den,
+                numeric_mismatch: None,
+            });
+        }
+
+        if num_val.is_nan() || den_val.is_nan() {
+            // `(a/b) / (c/d)` is NaN at a pole of both parts, as the derivatives of the
+            // rewritten `ln(x) * x` at 0 are, while `(a*d) / (b*c)` may well be `0/0`.
+            if let Some(crossed) = (refinements.cross_multiplied)(&num, &den) {
+                (num, den) = crossed;
+                continue;
+            }
+            // Undefined at the point itself, so sample beside it instead.
+            let value =
+                numeric_fallback((numerator, denominator), (&num, &den), at, config.epsilon)?;
+            if value.is_nan() {
+                return Err(LhopitalError::Undefined);
+            }
+            return Ok(LhopitalResult {
+                value,
+                iterations,
+                iterations_applied,
+                final_numerator: num,
+                final_denominator: den,
+                numeric_mismatch: None,
+            });
+        }
+
+        // Derivatives of parts that already overflow overflow as well, so differentiating
+        // would only repeat `∞/∞` while the trees grow.
+        if overflows(&num, at, config.max_magnitude) && overflows(&den, at, config.max_magnitude) {
+            return Err(LhopitalError::Overflow);
+        }
+
+        match classify(&num, &den, (num_val, den_val), at, config)? {
+            LhopitalStep::Differentiated {
+                numerator: next_num,
+                denominator: next_den,
+            } => {
+                if iterations_applied == max_iterations {
+                    return Err(LhopitalError::MaxIterationsExceeded);
+                }
+                #[cfg(debug_assertions)]
+                if config.verbose {
+                    check_derivative(&num, &next_num, x, out);
+                    check_derivative(&den, &next_den, x, out);
+                }
+                num = next_num;
+                den = next_den;
+                iterations_applied += 1;
+                (refinements.check_depth)(&num, config.max_depth)?;
+                (refinements.check_depth)(&den, config.max_depth)?;
+
+                // Derivatives that cycle back to an earlier quotient would repeat until
+                // `max_iterations`, so estimate the limit numerically instead.
+                let repeats = iterations
+                    .iter()
+                    .any(|step| step.numerator == num && step.denominator == den);
+                if repeats {
+                    let value = numeric_fallback(
+                        (numerator, denominator),
+                        (&num, &den),
+                        at,
+                        config.epsilon,
+                    )?;
+                    if value.is_nan() {
+                        return Err(LhopitalError::MaxIterationsExceeded);
+                    }
+                    return Ok(LhopitalResult {
+                        value,
+                        iterations,
+                        iterations_applied,
+                        final_numerator: num,
+                        final_denominator: den,
+                        numeric_mismatch: None,
+                    });
+                }
+            }
+            LhopitalStep::Value(value) => {
+                return Ok(LhopitalResult {
+                    value,
+                    iterations,
+                    iterations_applied,
+                    final_numerator: num,
+                    final_denominator: den,
+                    numeric_mismatch: if config.numeric_check {
+                        numeric_mismatch(numerator, denominator, at, value, config.epsilon)
+                    } else {
+                        None
+                    },
+                });
+            }
+        }
+    }
+
+    Err(LhopitalError::MaxIterationsExceeded)
+}
+
+// `(a*d, b*c)` for a quotient of two quotients `(a/b) / (c/d)`, where a reciprocal
+// `1/b`, a negative power `b^-n`, and a product or negation with one of them as a
+// factor count as quotients too. Returns `None` unless both parts are quotients.
+fn cross_multiplied(
+    numerator: &Expression,
+    denominator: &Expression,
+) -> Option<(Expression, Expression)> {
+    fn inverted(e: &Expression) -> Option<Rc<Expression>> {
+        match e {
+            Expression::Power(base, exp) if *exp < 0.0 => {
+                Some(Rc::new(Expression::Power(base.clone(), -exp)))
+            }
+            _ => None,
+        }
+    }
+    fn parts(e: &Expression) -> Option<(Expression, Rc<Expression>)> {
+        match e {
+            Expression::Quotient(num, den) => Some(((**num).clone(), den.clone())),
+            Expression::Reciprocal(den) => Some((Expression::one(), den.clone())),
+            Expression::Negate(a) => parts(a).map(|(num, den)| (-num, den)),
+            Expression::Product(a, b) => parts(b)
+                .map(|(num, den)| ((**a).clone() * num, den))
+                .or_else(|| parts(a).map(|(num, den)| (num * (**b).clone(), den))),
+            other => inverted(other).map(|den| (Expression::one(), den)),
+        }
+    }
+    let (a, b) = parts(numerator)?;
+    let (c, d) = parts(denominator)?;
+    Some((
+        Expression::Product(Rc::new(a), d).simplify(),
+        Expression::Product(b, Rc::new(c)).simplify(),
+    ))
+}
+
+// Warns in the trace when a symbolic derivative disagrees with a central difference of
+// the expression it came from, which points at a faulty differentiation rule. It costs
+// a few evaluations per derivative, so it only exists with debug assertions, and only
+// runs with a verbose trace to write to.
+#[cfg(debug_assertions)]
+fn check_derivative<T: Differentiable>(expr: &T, derivative: &T, at: f64, out: &mut dyn Write) {
+    // Off the point itself, where the expression may be undefined, by an offset that is
+    // unlikely to land on another singularity.
+    let x = at + 0.0731 * at.abs().max(1.0);
+    let symbolic = derivative.evaluate(x);
+    // A central difference is off by a multiple of h^2, so halving h shrinks its error
+    // fourfold. Extrapolating from both steps cancels that error, and how far the two
+    // steps disagree bounds what is left, however sharply the expression curves.
+    let h = 1e-5 * x.abs().max(1.0);
+    let coarse = central_difference(expr, x, h);
+    let fine = central_difference(expr, x, h / 2.0);
+    let numeric = (4.0 * fine - coarse) / 3.0;
+    let scale = symbolic.abs().max(numeric.abs()).max(1.0);
+    let allowed = 1e-4 * scale + (coarse - fine).abs();
+    if symbolic.is_finite() && numeric.is_finite() && (symbolic - numeric).abs() > allowed {
+        let _ = writeln!(
+            out,
+            "Warning: the derivative of {} is {} at x = {}, but a finite difference gives {}",
+            expr, symbolic, x, numeric
+        );
+    }
+}
+
+// Solves a quotient involving piecewise expressions on each side the point is
+// approached from, with every piecewise expression replaced by the branch holding just
+// beside the point on that side. The sides of a two-sided limit must agree.
+fn solve_piecewise(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: LimitPoint,
+    config: &SolveConfig,
+    out: &mut dyn Write,
+) -> Result<LhopitalResult, LhopitalError> {
+    let mut one_sided = |point: LimitPoint, side: Direction| {
+        let x = match point {
+            LimitPoint::PosInfinity => f64::INFINITY,
+            LimitPoint::NegInfinity => f64::NEG_INFINITY,
+            _ => point.sample_point(),
+        };
+        solve(
+            &branch_beside(numerator, x, side),
+            &branch_beside(denominator, x, side),
+            point,
+            config,
+            &mut *out,
+        )
+    };
+    match at {
+        LimitPoint::Finite(x) => {
+            let left = one_sided(LimitPoint::Left(x), Direction::Left)?;
+            let right = one_sided(LimitPoint::Right(x), Direction::Right)?;
+            let scale = left.value.abs().max(right.value.abs()).max(1.0);
+            if left.value == right.value
+                || (left.value - right.value).abs() <= config.tolerance * scale
+            {
+                Ok(right)
+            } else {
+                Err(LhopitalError::LimitDoesNotExist {
+                    left: left.value,
+                    right: right.value,
+                })
+            }
+        }
+        point => one_sided(point, point.direction()),
+    }
+}
+
+// Replaces every piecewise expression with its branch holding just beside `x` on
+// `side`, or NaN where no branch does.
+fn branch_beside(expr: &Expression, x: f64, side: Direction) -> Expression {
+    match expr {
+        Expression::Piecewise(branches) => branches
+            .iter()
+            .find(|(condition, _)| condition.holds_beside(x, side))
+            .map_or(Expression::Constant(f64::NAN), |(_, branch)| {
+                branch_beside(branch, x, side)
+            }),
+        other => other.map_children(|child| branch_beside(child, x, side)),
+    }
+}
+
+// Cross-checks a symbolic result against a numeric estimate of the original quotient,
+// which catches mistakes in the differentiation rules.
+fn numeric_mismatch<T: Differentiable>(
+    numerator: &T,
+    denominator: &T,
+    at: LimitPoint,
+    value: f64,
+    epsilon: f64,
+) -> Option<f64> {
+    if !value.is_finite() {
+        return None;
+    }
+    sample_beside(at, epsilon, |x| {
+        numerator.evaluate(x) / denominator.evaluate(x)
+    })
+    .ok()
+    .filter(|estimate| (estimate - value).abs() > 1e-3 * value.abs().max(1.0))
+}
+
+/// Solves the limit of a single expression, splitting a top-level quotient into its
+/// numerator and denominator. A top-level variable power is solved with
+/// [`lhopital_solve_power`], and any other expression is treated as being over 1.
+pub fn lhopital_solve_quotient(
+    expression: &Expression,
+    at: impl Into<LimitPoint>,
+    max_iterations: u32,
+) -> Result<f64, LhopitalError> {
+    match expression {
+        Expression::Quotient(num, den) => lhopital_solve(&**num, &**den, at, max_iterations),
+        Expression::PowerExpr(base, exp) => lhopital_solve_power(base, exp, at, max_iterations),
+        other => lhopital_solve(other, &Expression::Constant(1.0), at, max_iterations),
+    }
+}
+
+impl Expression {
+    /// The coefficients of the expression as a polynomial in `x`, lowest degree first,
+    /// or `None` if it is not one. This is the inverse of
+    /// [`Expression::from_polynomial`], so `x^2 - 4` gives `[-4.0, 0.0, 1.0]`.
+    ///
+    /// Any polynomial accepted by [`Expression::is_polynomial`] is multiplied out,
+    /// whatever its arrangement, so `(x + 1) * x` gives `[0.0, 1.0, 1.0]`. Trailing
+    /// zero coefficients are dropped, which leaves the zero polynomial with none at
+    /// all.
+    pub fn coefficients(&self) -> Option<Vec<f64>> {
+        if !self.is_polynomial() {
+            return None;
+        }
+        coefficients(self)
+    }
+
+    /// Whether the expression is a polynomial in `x`: built from constants, `x`, sums,
+    /// differences, products, whole-number powers and division by constants.
+    ///
+    /// [`lhopital_solve`] divides the common factor `x - at` out of a quotient of two
+    /// such polynomials directly, with [`try_factor_cancel`], instead of
+    /// differentiating them.
+    pub fn is_polynomial(&self) -> bool {
+        is_polynomial(self)
+    }
+
+    /// The degree of the expression as a polynomial in `x`, as accepted by
+    /// [`Expression::coefficients`], or `None` if it is not one.
+    ///
+    /// Terms that cancel do not count, so `x^2 - x^2 + x` has degree 1, and every
+    /// constant, zero included, has degree 0.
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients()
+            .map(|coefficients| coefficients.len().saturating_sub(1))
+    }
+
+    /// Evaluates the expression at `x` like [`Expression::evaluate`], except that a
+    /// product in the `0·∞` form or a sum or difference in the `∞-∞` form takes the
+    /// value of its limit at `x` instead of NaN, so `x * (1/x)` is 1 at 0.
+    ///
+    /// Such a node is solved with [`lhopital_solve_quotient`], and stays NaN if its
+    /// limit cannot be found.
+    pub fn evaluate_limit_safe(&self, x: f64) -> f64 {
+        if let Expression::Derivative(a) = self {
+            return a
+                .differentiate()
+                .map_or(f64::NAN, |derivative| derivative.evaluate_limit_safe(x));
+        }
+        let reduced = self.map_children(|child| Expression::Constant(child.evaluate_limit_safe(x)));
+        let value = reduced.evaluate(x);
+        if !value.is_nan() {
+            return value;
+        }
+        // A division by an exact zero evaluates to NaN rather than infinity.
+        let unbounded = |e: &Expression| {
+            let value = e.evaluate(x);
+            value.is_nan() || is_unbounded(value)
+        };
+        let indeterminate = match &reduced {
+            Expression::Product(a, b) => a.is_zero() || b.is_zero(),
+            Expression::Sum(a, b) | Expression::Difference(a, b) => unbounded(a) && unbounded(b),
+            _ => false,
+        };
+        if indeterminate {
+            lhopital_solve_quotient(self, x, DEFAULT_MAX_ITERATIONS).unwrap_or(f64::NAN)
+        } else {
+            value
+        }
+    }
+}
+
+/// Computes the limit of `base ^ exponent` as `x -> at`, where both may depend on `x`.
+///
+/// The indeterminate forms `1^∞`, `0^0` and `∞^0` are rewritten as
+/// `exp(lim ln(base) / exponent^-1)` and the inner `0/0` or `∞/∞` limit is solved with
+/// L'Hôpital's Rule. Any other form is evaluated directly.
+///
+/// At an infinite point a `1^∞` power is solved as `t -> 0` with `x = 1/t` where that
+/// turns every `1/x` into `t`, so `(1 + 1/x)^x` gives `e` to within rounding rather
+/// than its value at [`INFINITY_PROXY`].
+pub fn lhopital_solve_power(
+    base: &Expression,
+    exponent: &Expression,
+    at: impl Into<LimitPoint>,
+    max_iterations: u32,
+) -> Result<f64, LhopitalError> {
+    solve_power(base, exponent, at.into(), max_iterations).map(|(value, _)| value)
+}
+
+// The limit of `base ^ exponent`, and whether it was rewritten for L'Hôpital's Rule
+// rather than evaluated directly.
+fn solve_power(
+    base: &Expression,
+    exponent: &Expression,
+    at: LimitPoint,
+    max_iterations: u32,
+) -> Result<(f64, bool), LhopitalError> {
+    let at = at.validated()?;
+    let x = at.sample_point();
+    let base_val = base.evaluate(x);
+    let exp_val = exponent.evaluate(x);
+    // A part tending to zero at infinity, like `1/x`, is still well above the tolerance
+    // at the infinity proxy, so there the trend further out decides.
+    let limit_of = |e: &Expression| match at {
+        LimitPoint::PosInfinity => e.evaluate_at_infinity(1.0, INFINITY_PROXY),
+        LimitPoint::NegInfinity => e.evaluate_at_infinity(-1.0, INFINITY_PROXY),
+        _ => e.evaluate(x),
+    };
+    let is_zero = |e: &Expression| limit_of(e).abs() < DEFAULT_TOLERANCE;
+    let is_infinite = |e: &Expression| is_unbounded(limit_of(e));
+    let one_to_infinity = is_zero(&(base - 1.0)) && is_infinite(exponent);
+
+    // A side that cannot be evaluated at the point itself is also rewritten, so that
+    // the solver samples beside the point.
+    let indeterminate = one_to_infinity
+        || (is_zero(base) && is_zero(exponent))
+        || (is_infinite(base) && is_zero(exponent))
+        || base_val.is_nan()
+        || exp_val.is_nan();
+    if !indeterminate {
+        let value = base_val.powf(exp_val);
+        return if value.is_nan() {
+            Err(LhopitalError::Undefined)
+        } else {
+            Ok((value, false))
+        };
+    }
+
+    let log = Expression::Ln(Rc::new(base.clone()));
+    let reciprocal = Expression::Power(Rc::new(exponent.clone()), -1.0);
+    let config = SolveConfig::default().max_iterations(max_iterations);
+    if one_to_infinity && let Some(value) = solve_substituted(&log, &reciprocal, at, &config) {
+        return Ok((value.exp(), true));
+    }
+    let inner = solve(&log, &reciprocal, at, &config, &mut io::stdout())?;
+    Ok((inner.value.exp(), true))
+}
+
+// Solves a limit at infinity as `t -> 0` with `x = 1/t`, where the parts of a `1^∞`
+// power, like `ln(1 + 1/x)` and `1/x`, become `ln(1 + t)` and `t`. Sampled at the
+// infinity proxy instead, their quotient is off by about `1/x`. Returns `None` for a
+// finite point, or if `x` is left inside a reciprocal that is undefined at `t = 0`
+// so that the quotient cannot be evaluated there.
+fn solve_substituted(
+    numerator: &Expression,
+    denominator: &Expression,
+    at: LimitPoint,
+    config: &SolveConfig,
+) -> Option<f64> {
+    let point = match at {
+        LimitPoint::PosInfinity => LimitPoint::Right(0.0),
+        LimitPoint::NegInfinity => LimitPoint::Left(0.0),
+        _ => return None,
+    };
+    let t = Expression::Reciprocal(Rc::new(Expression::Variable));
+    let num = numerator.substitute(&t).simplify();
+    let den = denominator.substitute(&t).simplify();
+    if num.evaluate(0.0).is_nan() || den.evaluate(0.0).is_nan() {
+        return None;
+    }
+    // A quotient that had to be sampled beside `t = 0` is no better than one sampled
+    // at the infinity proxy.
+    let sampled = |step: &Step| step.numerator_value.is_nan() || step.denominator_value.is_nan();
+    solve(&num, &den, point, config, &mut io::sink())
+        .ok()
+        .filter(|result| !result.iterations.iter().any(sampled))
+        .map(|result| result.value)
+}
+
+/// How [`solve_all_forms`] found a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// The expression was determinate, so its value was read off without applying
+    /// L'Hôpital's Rule.
+    Determinate,
+    /// L'Hôpital's Rule was applied to a quotient in this form, after rewriting it
+    /// into a quotient for `0·∞` and `∞-∞`.
+    Quotient(DetectedForm),
+    /// A power in the `1^∞`, `0^0` or `∞^0` form was rewritten as
+    /// `exp(lim ln(base) / exponent^-1)`.
+    Power,
+}
+
+/// Finds the limit of `expression` as `x -> at` whatever indeterminate form it is in,
+/// returning the value and the [`Strategy`] that settled it.
+///
+/// A top-level quotient is solved as `0/0` or `∞/∞`, a top-level variable power as
+/// `1^∞`, `0^0` or `∞^0`, and anything else as a product or difference in the `0·∞`
+/// or `∞-∞` form, each with at most [`DEFAULT_MAX_ITERATIONS`] applications of the
+/// rule.
+pub fn solve_all_forms(
+    expression: &Expression,
+    at: impl Into<LimitPoint>,
+) -> Result<(f64, Strategy), LhopitalError> {
+    let at = at.into();
+    let (numerator, denominator) = match expression {
+        Expression::PowerExpr(base, exp) => {
+            let (value, rewritten) = solve_power(base, exp, at, DEFAULT_MAX_ITERATIONS)?;
+            let strategy = if rewritten {
+                Strategy::Power
+            } else {
+                Strategy::Determinate
+            };
+            return Ok((value, strategy));
+        }
+        Expression::Quotient(num, den) => (&**num, &**den),
+        other => (other, &Expression::Constant(1.0)),
+    };
+    let result = lhopital_solve_traced(numerator, denominator, at, DEFAULT_MAX_ITERATIONS)?;
+    let strategy = match result.iterations.first().and_then(|step| step.form) {
+        Some(form) => Strategy::Quotient(form),
+        None => Strategy::Determinate,
+    };
+    Ok((result.value, strategy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Expression {
+        Expression::parse(input).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        let scale = expected.abs().max(1.0);
+        assert!(
+            (actual - expected).abs() <= 1e-6 * scale,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn sin_x_over_x() {
+        let value = lhopital_solve_default(&parse("sin(x)"), &parse("x"), 0.0).unwrap();
+        assert_close(value, 1.0);
+    }
+
+    #[test]
+    fn exp_x_minus_one_over_x() {
+        let value = lhopital_solve_default(&parse("exp(x) - 1"), &parse("x"), 0.0).unwrap();
+        assert_close(value, 1.0);
+    }
+
+    #[test]
+    fn ln_x_over_x_minus_one() {
+        let value = lhopital_solve_default(&parse("ln(x)"), &parse("x - 1"), 1.0).unwrap();
+        assert_close(value, 1.0);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            lhopital_solve_default(&parse("1"), &parse("x * 0"), 0.0),
+            Err(LhopitalError::DivisionByZero)
+        );
+        assert_eq!(
+            lhopital_solve(&parse("1 - cos(x)"), &parse("x^2"), 0.0, 1),
+            Err(LhopitalError::MaxIterationsExceeded)
+        );
+        assert_eq!(
+            parse("sign(x)").differentiate(),
+            Err(LhopitalError::DifferentiationUnsupported(String::from(
+                "sign(x)"
+            )))
+        );
+    }
+
+    #[test]
+    fn limit_at_infinity() {
+        let value = lhopital_solve_default(&parse("x"), &parse("exp(x)"), f64::INFINITY).unwrap();
+        assert_close(value, 0.0);
+    }
+
+    #[test]
+    fn limits_at_infinity_follow_the_trend() {
+        // Sampled at the proxy alone these would come out as 1e-8, 2e-4, 1e8 and 5000.
+        let solve = |num: &str, den: &str| {
+            lhopital_solve_with(
+                &parse(num),
+                &parse(den),
+                LimitPoint::PosInfinity,
+                &SolveConfig::default(),
+            )
+            .map(|result| result.value)
+        };
+        assert_eq!(solve("ln(x)", "x"), Ok(0.0));
+        assert_eq!(solve("ln(x)", "sqrt(x)"), Ok(0.0));
+        assert_eq!(solve("x", "ln(x)"), Ok(f64::INFINITY));
+        assert_eq!(solve("sqrt(x)", "ln(x)"), Ok(f64::INFINITY));
+        assert_eq!(solve("1", "x"), Ok(0.0));
+        // A quotient tending to a nonzero constant keeps its sampled value.
+        assert_close(solve("2 * x + 1", "x").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn infinity_over_infinity() {
+        let (num, den) = (parse("x^2"), parse("exp(x)"));
+        let result = lhopital_solve_traced(&num, &den, f64::INFINITY, 20).unwrap();
+        assert_close(result.value, 0.0);
+        assert_eq!(result.iterations[0].form, Some(DetectedForm::InfOverInf));
+        assert!(result.iterations_applied >= 2);
+    }
+
+    #[test]
+    fn limit_at_negative_infinity() {
+        // exp(x) underflows to 0 at the proxy, so the sign comes from its signed zero.
+        let exp = parse("exp(x)");
+        assert_eq!(
+            lhopital_solve_default(&parse("x^2"), &exp, f64::NEG_INFINITY),
+            Ok(f64::INFINITY)
+        );
+        assert_eq!(
+            lhopital_solve_default(&parse("x^3"), &exp, f64::NEG_INFINITY),
+            Ok(f64::NEG_INFINITY)
+        );
+        assert_eq!(
+            lhopital_solve_default(&parse("x^2"), &parse("-exp(x)"), f64::NEG_INFINITY),
+            Ok(f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn zero_times_infinity() {
+        let value =
+            lhopital_solve_quotient(&parse("x * ln(x)"), LimitPoint::Right(0.0), 20).unwrap();
+        assert!(value.abs() < DEFAULT_TOLERANCE, "{}", value);
+    }
+
+    #[test]
+    fn one_sided_limits() {
+        let (num, den) = (parse("x"), parse("abs(x)"));
+        assert_eq!(
+            lhopital_solve_default(&num, &den, LimitPoint::Left(0.0)),
+            Ok(-1.0)
+        );
+        assert_eq!(
+            lhopital_solve_default(&num, &den, LimitPoint::Right(0.0)),
+            Ok(1.0)
+        );
+        assert!(matches!(
+            lhopital_solve_default(&num, &den, 0.0),
+            Err(LhopitalError::LimitDoesNotExist { .. })
+        ));
+    }
+
+    #[test]
+    fn numeric_check_agrees() {
+        let (num, den) = (parse("x^2 - 4"), parse("x - 2"));
+        let result = lhopital_solve_traced(&num, &den, 2.0, 20).unwrap();
+        assert_close(result.value, 4.0);
+        assert_eq!(result.numeric_mismatch, None);
+        assert_close(
+            numeric_limit(&num, &den, 2.0, NUMERIC_EPSILON).unwrap(),
+            4.0,
+        );
+    }
+
+    #[test]
+    fn configurable_tolerance() {
+        let (num, den) = (parse("sin(x) + 0.00000001"), parse("x"));
+        assert!(matches!(
+            lhopital_solve_with_tolerance(&num, &den, 0.0, 20, DEFAULT_TOLERANCE),
+            Err(LhopitalError::InfiniteLimit { .. })
+        ));
+        let value = lhopital_solve_with_tolerance(&num, &den, 0.0, 20, 1e-6).unwrap();
+        assert_close(value, 1.0);
+    }
+
+    #[test]
+    fn square_root() {
+        let value = lhopital_solve_default(&parse("sqrt(1 + x) - 1"), &parse("x"), 0.0).unwrap();
+        assert_close(value, 0.5);
+    }
+
+    #[test]
+    fn deeply_nested_expression() {
+        let mut chain = Expression::Variable;
+        for _ in 0..100_000 {
+            chain = Expression::Sum(Rc::new(chain), Rc::new(Expression::Variable));
+        }
+        assert_eq!(
+            lhopital_solve_default(&chain, &parse("x"), 0.0),
+            Err(LhopitalError::TooDeep {
+                limit: crate::DEFAULT_MAX_DEPTH
+            })
+        );
+    }
+
+    #[test]
+    fn cycling_derivatives_stop_early() {
+        // Both parts are identically zero but not recognisably so, and their
+        // derivatives cycle through the same four quotients.
+        let (num, den) = (parse("exp(x) - exp(x)"), parse("sin(x) - sin(x)"));
+        let config = SolveConfig::default().max_iterations(1000).verbose(true);
+        let mut out = Vec::new();
+        let result = solve_with_trace_to_writer(&num, &den, 0.0, &config, &mut out);
+        assert!(result.is_err());
+        let trace = String::from_utf8(out).unwrap();
+        assert!(trace.lines().count() <= 5, "{}", trace);
+    }
+
+    #[test]
+    fn tan_x_over_x() {
+        let value = lhopital_solve_default(&parse("tan(x)"), &parse("x"), 0.0).unwrap();
+        assert_close(value, 1.0);
+    }
+
+    #[test]
+    fn negative_base_with_fractional_power() {
+        let root = parse("x^0.5");
+        assert!(root.evaluate(-2.0).is_nan());
+        assert_eq!(
+            lhopital_solve_default(&root, &parse("1"), -2.0),
+            Err(LhopitalError::Undefined)
+        );
+    }
+
+    #[test]
+    fn apply_once() {
+        let step = apply_lhopital_once(&parse("x^2 - 4"), &parse("x - 2"), 2.0).unwrap();
+        assert_eq!(
+            step,
+            LhopitalStep::Differentiated {
+                numerator: parse("2 * x"),
+                denominator: parse("1"),
+            }
+        );
+        assert_eq!(
+            apply_lhopital_once(&parse("2 * x"), &parse("1"), 2.0),
+            Ok(LhopitalStep::Value(4.0))
+        );
+    }
+
+    #[test]
+    fn misapplied_rule() {
+        assert_eq!(
+            assert_indeterminate(&parse("sin(x)"), &parse("x"), 0.0),
+            Ok(())
+        );
+        assert_eq!(
+            assert_indeterminate(&parse("x + 1"), &parse("x + 2"), 0.0),
+            Err(LhopitalError::Determinate(0.5))
+        );
+        assert_eq!(
+            assert_indeterminate(&parse("x + 1"), &parse("x"), 0.0),
+            Err(LhopitalError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn counts_applications() {
+        let config = SolveConfig::default().cancel_factors(false);
+        let result = lhopital_solve_with(&parse("x^2 - 4"), &parse("x - 2"), 2.0, &config).unwrap();
+        assert_eq!(result.iterations_applied, 1);
+        let result =
+            lhopital_solve_with(&parse("1 - cos(x)"), &parse("x^2"), 0.0, &config).unwrap();
+        assert_eq!(result.iterations_applied, 2);
+        assert_close(result.value, 0.5);
+    }
+
+    #[test]
+    fn one_to_the_infinity() {
+        let (base, exponent) = (parse("1 + 1/x"), parse("x"));
+        let value = lhopital_solve_power(&base, &exponent, f64::INFINITY, 20).unwrap();
+        assert!((value - std::f64::consts::E).abs() < 1e-9, "{}", value);
+        let value = lhopital_solve_power(&parse("1 - 2/x"), &exponent, f64::INFINITY, 20).unwrap();
+        assert!((value - (-2.0f64).exp()).abs() < 1e-9, "{}", value);
+    }
+
+    #[test]
+    fn infinity_minus_infinity() {
+        let one = parse("1");
+        let value = lhopital_solve_default(&parse("1/x - 1/sin(x)"), &one, 0.0).unwrap();
+        assert_close(value, 0.0);
+        let value = lhopital_solve_default(&parse("1/x - 1/(exp(x) - 1)"), &one, 0.0).unwrap();
+        assert_close(value, 0.5);
+        assert_eq!(
+            combine_fractions(&parse("1/x - 1/sin(x)")),
+            Some(parse("(1 * sin(x) - 1 * x) / (x * sin(x))"))
+        );
+    }
+
+    #[test]
+    fn custom_config() {
+        let config = SolveConfig::default()
+            .max_iterations(5)
+            .tolerance(1e-12)
+            .numeric_check(false)
+            .epsilon(1e-7);
+        let result =
+            lhopital_solve_with(&parse("1 - cos(x)"), &parse("x^2"), 0.0, &config).unwrap();
+        assert_close(result.value, 0.5);
+        let config = config.max_iterations(1);
+        assert_eq!(
+            lhopital_solve_with(&parse("1 - cos(x)"), &parse("x^2"), 0.0, &config).map(|r| r.value),
+            Err(LhopitalError::MaxIterationsExceeded)
+        );
+    }
+
+    #[test]
+    fn signed_infinities() {
+        let (one, den) = (parse("1"), parse("x - 2"));
+        assert_eq!(
+            lhopital_solve_default(&one, &den, LimitPoint::Left(2.0)),
+            Ok(f64::NEG_INFINITY)
+        );
+        assert_eq!(
+            lhopital_solve_default(&one, &den, LimitPoint::Right(2.0)),
+            Ok(f64::INFINITY)
+        );
+        assert_eq!(
+            lhopital_solve_default(&one, &den, 2.0),
+            Err(LhopitalError::InfiniteLimit {
+                left: f64::NEG_INFINITY,
+                right: f64::INFINITY
+            })
+        );
+        assert_eq!(
+            lhopital_solve_default(&one, &parse("x^2"), 0.0),
+            Ok(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn absolute_value() {
+        let (num, den) = (parse("abs(x)"), parse("x"));
+        assert_eq!(
+            lhopital_solve_default(&num, &den, LimitPoint::Right(0.0)),
+            Ok(1.0)
+        );
+        assert_eq!(
+            lhopital_solve_default(&num, &den, LimitPoint::Left(0.0)),
+            Ok(-1.0)
+        );
+    }
+
+    #[test]
+    fn hyperbolic_sine() {
+        let value = lhopital_solve_default(&parse("sinh(x)"), &parse("x"), 0.0).unwrap();
+        assert_close(value, 1.0);
+    }
+
+    #[test]
+    fn arctangent() {
+        let value = lhopital_solve_default(&parse("atan(x)"), &parse("x"), 0.0).unwrap();
+        assert_close(value, 1.0);
+        // d/dx(atan(x^2)) = 2x / (1 + x^4)
+        let derivative = parse("atan(x^2)").differentiate().unwrap();
+        assert_close(derivative.evaluate(1.0), 1.0);
+        assert_close(derivative.evaluate(0.5), 1.0 / 1.0625);
+    }
+
+    #[test]
+    fn final_derivatives() {
+        let result = lhopital_solve_traced(&parse("x^2 - 4"), &parse("x - 2"), 2.0, 20).unwrap();
+        assert_eq!(result.final_numerator, parse("x + 2"));
+        assert_eq!(result.final_denominator, parse("1"));
+
+        let config = SolveConfig::default().cancel_factors(false);
+        let result = lhopital_solve_with(&parse("x^2 - 4"), &parse("x - 2"), 2.0, &config).unwrap();
+        assert_eq!(result.final_numerator, parse("2 * x"));
+        assert_eq!(result.final_denominator, parse("1"));
+    }
+
+    #[test]
+    fn classifies_quotients() {
+        let cases = [
+            ("x^2 - 4", "x - 2", 2.0, QuotientForm::Removable),
+            ("x + 1", "x", 0.0, QuotientForm::Pole),
+            ("x", "x^2", 0.0, QuotientForm::Pole),
+            ("sin(x)", "x", 0.0, QuotientForm::Indeterminate0_0),
+            ("x + 1", "x + 2", 0.0, QuotientForm::Determinate),
+        ];
+        for (num, den, at, form) in cases {
+            assert_eq!(classify_form(&parse(num), &parse(den), at), Ok(form));
+        }
+        assert_eq!(
+            classify_form(&parse("ln(x)"), &parse("x"), -1.0),
+            Err(LhopitalError::Undefined)
+        );
+    }
+
+    #[test]
+    fn default_iterations() {
+        let examples = [
+            ("x^2 - 4", "x - 2", 2.0, 4.0),
+            ("sin(x)", "x", 0.0, 1.0),
+            ("1 - cos(x)", "x^2", 0.0, 0.5),
+            ("x^3", "exp(x)", f64::INFINITY, 0.0),
+        ];
+        for (num, den, at, limit) in examples {
+            assert_close(
+                lhopital_solve_default(&parse(num), &parse(den), at).unwrap(),
+                limit,
+            );
+        }
+    }
+
+    #[test]
+    fn reports_the_detected_form() {
+        let result =
+            lhopital_solve_traced(&parse("x^2"), &parse("exp(x)"), f64::INFINITY, 20).unwrap();
+        assert_eq!(result.iterations[0].form, Some(DetectedForm::InfOverInf));
+        assert_eq!(DetectedForm::InfOverInf.to_string(), "∞/∞");
+        let result = lhopital_solve_traced(&parse("sin(x)"), &parse("x"), 0.0, 20).unwrap();
+        assert_eq!(result.iterations[0].form, Some(DetectedForm::ZeroOverZero));
+        assert_eq!(result.iterations.last().unwrap().form, None);
+    }
+
+    #[test]
+    fn nonzero_over_vanishing_denominator() {
+        let (num, den) = (parse("x + 1"), parse("x - 1"));
+        assert_eq!(
+            lhopital_solve_default(&num, &den, 1.0),
+            Err(LhopitalError::InfiniteLimit {
+                left: f64::NEG_INFINITY,
+                right: f64::INFINITY
+            })
+        );
+        assert_eq!(
+            assert_indeterminate(&num, &den, 1.0),
+            Err(LhopitalError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn zero_iterations() {
+        assert_eq!(
+            lhopital_solve(&parse("x + 1"), &parse("x + 2"), 0.0, 0),
+            Ok(0.5)
+        );
+        assert_eq!(
+            lhopital_solve(&parse("sin(x)"), &parse("x"), 0.0, 0),
+            Err(LhopitalError::MaxIterationsExceeded)
+        );
+    }
+
+    #[test]
+    fn nan_limit_point() {
+        let (num, den) = (parse("sin(x)"), parse("x"));
+        assert_eq!(
+            lhopital_solve(&num, &den, f64::NAN, u32::MAX),
+            Err(LhopitalError::InvalidLimitPoint(String::from("NaN")))
+        );
+        assert_eq!(
+            lhopital_solve(&num, &den, LimitPoint::Right(f64::NAN), u32::MAX),
+            Err(LhopitalError::InvalidLimitPoint(String::from("NaN+")))
+        );
+        assert_close(
+            lhopital_solve_default(&num, &den, f64::INFINITY).unwrap(),
+            0.0,
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn broken_derivative_is_reported() {
+        let expr = parse("sin(x)");
+        let mut trace = Vec::new();
+        check_derivative(&expr, &expr.differentiate().unwrap(), 0.0, &mut trace);
+        assert!(trace.is_empty());
+
+        // d/dx(sin(x)) mistaken for -cos(x), as a broken trigonometric rule would give.
+        check_derivative(&expr, &parse("-cos(x)"), 0.0, &mut trace);
+        let trace = String::from_utf8(trace).unwrap();
+        assert!(
+            trace.starts_with("Warning: the derivative of sin(x) is "),
+            "{}",
+            trace
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn derivative_check_stays_quiet() {
+        // The check only writes to a verbose trace.
+        let (num, den) = (parse("sin(10000 * x)"), parse("x"));
+        let mut trace = Vec::new();
+        let result =
+            solve_with_trace_to_writer(&num, &den, 0.0, &SolveConfig::default(), &mut trace);
+        assert_eq!(result.map(|result| result.value), Ok(10000.0));
+        assert!(trace.is_empty());
+
+        // A rapidly oscillating function is far from linear over the finite difference
+        // step, which must not be mistaken for a faulty rule.
+        let config = SolveConfig::default().verbose(true);
+        solve_with_trace_to_writer(&num, &den, 0.0, &config, &mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        assert!(!trace.contains("Warning"), "{}", trace);
+    }
+
+    #[test]
+    fn orders() {
+        assert_eq!(
+            lhopital_order(&parse("sin(x)"), &parse("x"), 0.0, 10),
+            Ok(1)
+        );
+        assert_eq!(
+            lhopital_order(&parse("1 - cos(x)"), &parse("x^2"), 0.0, 10),
+            Ok(2)
+        );
+        assert_eq!(
+            lhopital_order(&parse("x + 1"), &parse("x + 2"), 0.0, 10),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn single_cancellation() {
+        let (num, den) = (parse("x^2 - 4"), parse("x - 2"));
+        let (cancelled_num, cancelled_den) = try_factor_cancel(&num, &den, 2.0).unwrap();
+        assert_close(
+            cancelled_num.evaluate(2.0) / cancelled_den.evaluate(2.0),
+            4.0,
+        );
+
+        let mut trace = Vec::new();
+        let result = solve_with_trace_to_writer(
+            &num,
+            &den,
+            2.0,
+            &SolveConfig::default().verbose(true),
+            &mut trace,
+        )
+        .unwrap();
+        assert_close(result.value, 4.0);
+        assert_eq!(result.iterations_applied, 0);
+        let trace = String::from_utf8(trace).unwrap();
+        assert_eq!(trace.matches("Cancelled x - 2").count(), 1, "{}", trace);
+    }
+
+    #[test]
+    fn trace_precision() {
+        let trace = |config: &SolveConfig| {
+            let mut out = Vec::new();
+            solve_with_trace_to_writer(&parse("sin(x)"), &parse("x"), 0.0, config, &mut out)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let config = SolveConfig::default().verbose(true);
+        assert!(trace(&config).contains("at x = 0: 1 / 1\n"));
+        assert!(trace(&config.precision(8)).contains("at x = 0: 1.00000000 / 1.00000000\n"));
+    }
+
+    #[test]
+    fn relative_and_absolute_tolerance() {
+        // `sqrt(x)^4` rounds to just above 4 at 2, far above the absolute tolerance
+        // once scaled up.
+        let (num, den) = (parse("1000000000000 * (sqrt(x)^4 - 4)"), parse("x - 2"));
+        let solve = |config: &SolveConfig| {
+            solve_with_trace_to_writer(&num, &den, 2.0, config, &mut io::sink())
+                .map(|result| result.value)
+        };
+        assert!(matches!(
+            solve(&SolveConfig::default()),
+            Err(LhopitalError::InfiniteLimit { .. })
+        ));
+        let relative = solve(&SolveConfig::default().relative_tolerance(1e-9)).unwrap();
+        assert!((relative / 4e12 - 1.0).abs() < 1e-9, "{}", relative);
+    }
+
+    #[test]
+    fn solves_every_form() {
+        let cases = [
+            (
+                "sin(x) / x",
+                LimitPoint::Finite(0.0),
+                1.0,
+                Strategy::Quotient(DetectedForm::ZeroOverZero),
+            ),
+            (
+                "(x^2 + 1) / (2 * x^2)",
+                LimitPoint::PosInfinity,
+                0.5,
+                Strategy::Quotient(DetectedForm::InfOverInf),
+            ),
+            (
+                "x * ln(x)",
+                LimitPoint::Right(0.0),
+                0.0,
+                Strategy::Quotient(DetectedForm::ZeroTimesInf),
+            ),
+            (
+                "1 / x - 1 / sin(x)",
+                LimitPoint::Finite(0.0),
+                0.0,
+                Strategy::Quotient(DetectedForm::InfMinusInf),
+            ),
+            (
+                "(1 + 1/x)^x",
+                LimitPoint::PosInfinity,
+                std::f64::consts::E,
+                Strategy::Power,
+            ),
+            ("x^x", LimitPoint::Right(0.0), 1.0, Strategy::Power),
+            ("x^(1/x)", LimitPoint::PosInfinity, 1.0, Strategy::Power),
+            ("x + 1", LimitPoint::Finite(2.0), 3.0, Strategy::Determinate),
+        ];
+        for (input, at, expected, strategy) in cases {
+            let (value, found) = solve_all_forms(&parse(input), at).unwrap();
+            assert!((value - expected).abs() < 1e-6, "{}: {}", input, value);
+            assert_eq!(found, strategy, "{}", input);
+        }
+    }
+
+    #[test]
+    fn double_root() {
+        let (num, den) = (parse("x^3 - 3*x + 2"), parse("x^2 - 2*x + 1"));
+        assert_close(lhopital_solve_default(&num, &den, 1.0).unwrap(), 3.0);
+        assert_eq!(lhopital_order(&num, &den, 1.0, 10), Ok(2));
+
+        // The same double root far from zero, where the quotient after one
+        // differentiation is only 0/0 up to rounding error.
+        let (num, den) = (num.shift(-999.1), den.shift(-999.1));
+        assert_close(lhopital_solve_default(&num, &den, 1000.1).unwrap(), 3.0);
+        assert_eq!(lhopital_order(&num, &den, 1000.1, 10), Ok(2));
+    }
+
+    #[test]
+    fn removable_zero_times_infinity() {
+        let expr = parse("x * (1/x)");
+        assert!(expr.evaluate(0.0).is_nan());
+        assert_close(expr.evaluate_limit_safe(0.0), 1.0);
+        assert_close(expr.evaluate_limit_safe(0.001), 1.0);
+        assert_close(parse("2 + x * (1/x)").evaluate_limit_safe(0.0), 3.0);
+    }
+
+    #[test]
+    fn limit_or_none() {
+        assert_eq!(limit(&parse("x^2 - 4"), &parse("x - 2"), 2.0), Some(4.0));
+        assert_eq!(limit(&parse("x + 1"), &parse("x - 1"), 1.0), None);
+    }
+
+    #[test]
+    fn polynomial_degree() {
+        assert_eq!(parse("x^2 - 4").degree(), Some(2));
+        assert_eq!(parse("sin(x)").degree(), None);
+        assert_eq!(Expression::Constant(3.0).degree(), Some(0));
+        assert_eq!(parse("x^2 - x^2 + x").degree(), Some(1));
+    }
+
+    #[test]
+    fn sign_dependent_limit() {
+        let (num, den) = (parse("x"), parse("abs(x)"));
+        assert_eq!(
+            lhopital_solve_default(&num, &den, 0.0),
+            Err(LhopitalError::LimitDoesNotExist {
+                left: -1.0,
+                right: 1.0
+            })
+        );
+        assert_close(
+            lhopital_solve_default(&num, &den, LimitPoint::Left(0.0)).unwrap(),
+            -1.0,
+        );
+        assert_close(
+            lhopital_solve_default(&num, &den, LimitPoint::Right(0.0)).unwrap(),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn probes_each_form() {
+        let report = |num: &str, den: &str, at: LimitPoint| probe(&parse(num), &parse(den), at);
+        let zero = LimitPoint::Finite(0.0);
+        assert_eq!(
+            report("sin(x)", "x", zero),
+            FormReport {
+                form: ProbedForm::ZeroOverZero,
+                numerator_value: 0.0,
+                denominator_value: 0.0
+            }
+        );
+        let inf = report("x^2", "x^3", LimitPoint::PosInfinity);
+        assert_eq!(inf.form, ProbedForm::InfOverInf);
+        assert_eq!((inf.numerator_value, inf.denominator_value), (1e16, 1e24));
+        assert_eq!(
+            report("x + 1", "x", zero),
+            FormReport {
+                form: ProbedForm::ConstantOverZero,
+                numerator_value: 1.0,
+                denominator_value: 0.0
+            }
+        );
+        assert_eq!(
+            report("x", "x + 1", zero),
+            FormReport {
+                form: ProbedForm::ZeroOverConstant,
+                numerator_value: 0.0,
+                denominator_value: 1.0
+            }
+        );
+        assert_eq!(
+            report("x + 2", "x + 1", zero),
+            FormReport {
+                form: ProbedForm::ConstantOverConstant,
+                numerator_value: 2.0,
+                denominator_value: 1.0
+            }
+        );
+        let undefined = report("ln(x)", "x", LimitPoint::Finite(-1.0));
+        assert_eq!(undefined.form, ProbedForm::Undefined);
+        assert!(undefined.numerator_value.is_nan());
+        assert_eq!(undefined.denominator_value, -1.0);
+    }
+
+    #[test]
+    fn polynomial_coefficients() {
+        assert_eq!(parse("x^2 - 4").coefficients(), Some(vec![-4.0, 0.0, 1.0]));
+        assert_eq!(parse("sin(x) + 1").coefficients(), None);
+    }
+
+    #[test]
+    fn runaway_values() {
+        let exploding = |num: &str, den: &str| {
+            lhopital_solve_default(&parse(num), &parse(den), LimitPoint::PosInfinity)
+        };
+        assert_eq!(
+            exploding("exp(x^2)", "exp(x^3)"),
+            Err(LhopitalError::Overflow)
+        );
+        assert_eq!(exploding("x^200", "x^300"), Err(LhopitalError::Overflow));
+    }
+
+    #[test]
+    fn batch() {
+        let problems = [
+            (parse("sin(x)"), parse("x"), 0.0),
+            (parse("x^2 - 4"), parse("x - 2"), 2.0),
+            (parse("x + 1"), parse("x - 1"), 1.0),
+            // Symbols named like `e` and `x` must survive the trip to another thread.
+            (
+                Expression::Symbol('e') * Expression::var(),
+                Expression::Symbol('x') + Expression::var(),
+                0.0,
+            ),
+            (Expression::Constant(f64::INFINITY), Expression::one(), 0.0),
+        ];
+        let sequential: Vec<_> = problems
+            .iter()
+            .map(|(num, den, at)| lhopital_solve_default(num, den, *at))
+            .collect();
+        assert_eq!(solve_batch(&problems), sequential);
+        assert_close(sequential[0].clone().unwrap(), 1.0);
+        assert_close(sequential[1].clone().unwrap(), 4.0);
+        assert!(matches!(
+            sequential[2],
+            Err(LhopitalError::InfiniteLimit { .. })
+        ));
+    }
+
+    #[test]
+    fn identically_zero_parts() {
+        assert_eq!(
+            lhopital_solve_default(&Expression::zero(), &Expression::zero(), 1.0),
+            Err(LhopitalError::Undefined)
+        );
+        assert_eq!(
+            lhopital_solve_default(&parse("0 * sin(x)"), &parse("0 * x"), 0.0),
+            Err(LhopitalError::Undefined)
+        );
+    }
+
+    #[test]
+    fn non_finite_constants() {
+        let inf = Expression::Constant(f64::INFINITY);
+        assert!(matches!(
+            lhopital_solve_default(&inf, &parse("x"), 1.0),
+            Err(LhopitalError::DomainError(_))
+        ));
+        assert!(matches!(
+            lhopital_solve_default(&parse("x"), &Expression::Constant(f64::NAN), 1.0),
+            Err(LhopitalError::DomainError(_))
+        ));
+        assert!(matches!(
+            lhopital_solve_default(&(inf * Expression::var()), &parse("x"), 0.0),
+            Err(LhopitalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn deterministic_traces() {
+        let trace = || {
+            let mut out = Vec::new();
+            let config = SolveConfig::default().verbose(true);
+            solve_with_trace_to_writer(&parse("1 - cos(x)"), &parse("x^2"), 0.0, &config, &mut out)
+                .unwrap();
+            out
+        };
+        let first = trace();
+        assert!(!first.is_empty());
+        assert_eq!(first, trace());
+
+        // NaN never equals itself, so the samples are compared as printed.
+        let (num, den) = (parse("sin(x)"), parse("x"));
+        let samples = |offsets: &[f64]| format!("{:?}", sample_around(&num, &den, 0.0, offsets));
+        assert_eq!(
+            samples(&[0.1, -0.1, f64::NAN, 0.1, -0.0]),
+            samples(&[f64::NAN, 0.0, -0.1, 0.1])
+        );
+    }
+
+    #[test]
+    fn trace_to_writer() {
+        let mut out = Vec::new();
+        let config = SolveConfig::default().verbose(true);
+        let result =
+            solve_with_trace_to_writer(&parse("1 - cos(x)"), &parse("x^2"), 0.0, &config, &mut out)
+                .unwrap();
+        assert_close(result.value, 0.5);
+        let trace = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = trace.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", trace);
+        assert!(lines[0].starts_with("Iteration 0: (1 - cos(x)) / (x^2) at x = 0"));
+        assert!(lines[1].starts_with("Iteration 1: "));
+        assert!(lines[2].starts_with("Iteration 2: "));
+    }
+
+    #[test]
+    fn convergence_table() {
+        let table = limit_table(
+            &parse("x^2 - 4"),
+            &parse("x - 2"),
+            2.0,
+            &[0.1, 0.0, 0.01, 0.001],
+        );
+        assert_eq!(table.len(), 6);
+        assert!(table.windows(2).all(|rows| rows[0].0 < rows[1].0));
+        for (x, value) in table {
+            assert!(
+                (value - 4.0).abs() <= (x - 2.0).abs() + 1e-9,
+                "{} at {}",
+                value,
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn cancels_without_differentiating() {
+        for (num, den, at, expected) in [
+            ("x^2 - 4", "x - 2", 2.0, 4.0),
+            ("(x - 2) * (x + 2)", "x - 2", 2.0, 4.0),
+            ("(x + 1)^2 - 1", "x", 0.0, 2.0),
+            ("x^3 - 8", "x^2 - 4", 2.0, 3.0),
+        ] {
+            let result = lhopital_solve_traced(&parse(num), &parse(den), at, 10).unwrap();
+            assert_eq!(result.value, expected, "({}) / ({})", num, den);
+            assert_eq!(result.iterations_applied, 0, "({}) / ({})", num, den);
+        }
+    }
+
+    #[test]
+    fn simplifies_before_cancelling() {
+        let den = parse("x - 2");
+        let result = lhopital_solve_traced(&parse("x^2 - 4 + 0 * sin(x)"), &den, 2.0, 10).unwrap();
+        assert_eq!((result.value, result.iterations_applied), (4.0, 0));
+
+        // 0 * ln(x) is undefined for x <= 0, so it is not simplified away and the
+        // quotient is differentiated instead.
+        let result = lhopital_solve_traced(&parse("x^2 - 4 + 0 * ln(x)"), &den, 2.0, 10).unwrap();
+        assert_close(result.value, 4.0);
+        assert_eq!(result.iterations_applied, 1);
+        assert_eq!(
+            lhopital_solve_default(&parse("x + 0 * ln(x)"), &parse("x"), LimitPoint::Left(0.0)),
+            Err(LhopitalError::Undefined)
+        );
+    }
+
+    #[test]
+    fn preconditions() {
+        assert_eq!(
+            lhopital_applicable(&parse("sin(x)"), &parse("x"), 0.0),
+            Ok(())
+        );
+        assert_eq!(
+            lhopital_applicable(&parse("x^5"), &parse("x^4"), 0.0),
+            Ok(())
+        );
+        assert_eq!(
+            lhopital_applicable(&parse("x"), &parse("x^3 - x^3"), 0.0),
+            Err(LhopitalError::DivisionByZero)
+        );
+        assert!(matches!(
+            lhopital_applicable(&parse("sign(x - 1) * x"), &parse("x"), 0.0),
+            Err(LhopitalError::DifferentiationUnsupported(_))
+        ));
+    }
+
+    #[test]
+    fn polynomial_path_matches_the_general_one() {
+        assert!(parse("x^3 - 3*x + 2").is_polynomial());
+        assert!(!parse("x * sin(x)").is_polynomial());
+        let general = SolveConfig::default().cancel_factors(false);
+        for (num, den, at) in [
+            ("x^3 - 3*x + 2", "x^2 - 2*x + 1", 1.0),
+            ("(x - 1)^3", "(x - 1)^2 * (x + 1)", 1.0),
+            ("x^2 - 4", "x - 2", 2.0),
+            ("x^4 - 1", "x^3 - 1", 1.0),
+        ] {
+            let (num, den) = (parse(num), parse(den));
+            let specialized = lhopital_solve_traced(&num, &den, at, 10).unwrap();
+            let differentiated =
+                solve_with_trace_to_writer(&num, &den, at, &general, &mut io::sink()).unwrap();
+            assert_eq!(specialized.iterations_applied, 0);
+            assert!(differentiated.iterations_applied > 0);
+            assert_close(specialized.value, differentiated.value);
+        }
+    }
+
+    #[test]
+    fn recognizes_closed_forms() {
+        assert_eq!(
+            recognize_constant(std::f64::consts::E + 0.000004, 0.00001),
+            Some("e")
+        );
+        assert_eq!(recognize_constant(0.5, DEFAULT_TOLERANCE), Some("1/2"));
+        assert_eq!(
+            recognize_constant(std::f64::consts::PI, DEFAULT_TOLERANCE),
+            Some("π")
+        );
+        assert_eq!(recognize_constant(4.0, DEFAULT_TOLERANCE), None);
+    }
+
+    #[test]
+    fn same_function_short_circuits() {
+        for (num, den) in [("sin(x)", "sin(x)"), ("x + sin(x)", "sin(x) + x")] {
+            let result = lhopital_solve_traced(&parse(num), &parse(den), 0.0, 10).unwrap();
+            assert_eq!(result.value, 1.0);
+            assert_eq!(result.iterations_applied, 0, "({}) / ({})", num, den);
+        }
+        assert_eq!(
+            lhopital_solve_default(&parse("ln(x)"), &parse("ln(x)"), -1.0),
+            Err(LhopitalError::Undefined)
+        );
+    }
+
+    #[test]
+    fn derivatives_short_circuit_once_equal() {
+        // Both derivatives are 1 - cos(x), still 0/0 at 0, which would otherwise take two
+        // more applications of the rule.
+        let result =
+            lhopital_solve_traced(&parse("x - sin(x)"), &parse("x - sin(x) + 0 * x"), 0.0, 10)
+                .unwrap();
+        assert_eq!(result.value, 1.0);
+        assert_eq!(result.iterations_applied, 1);
+        assert_eq!(result.final_numerator, result.final_denominator);
+        assert_eq!(result.final_numerator, parse("1 - cos(x)"));
+    }
+
+    #[test]
+    fn same_function_short_circuits_at_infinity() {
+        // exp(x) overflows at the infinity proxy, which once stopped these with Overflow.
+        let exp = parse("exp(x)");
+        assert_eq!(lhopital_solve_default(&exp, &exp, f64::INFINITY), Ok(1.0));
+        // Both derivatives are 2x + 1/x, up to the order of the terms.
+        let result = lhopital_solve_traced(
+            &parse("x^2 + ln(x)"),
+            &parse("ln(x) + x^2 + 0 * x"),
+            f64::INFINITY,
+            10,
+        )
+        .unwrap();
+        assert_eq!((result.value, result.iterations_applied), (1.0, 1));
+    }
+}
@@ -0,0 +1,222 @@
+use std::rc::Rc;
+
+use crate::{Condition, Expression};
+
+/// A traversal over an [`Expression`], with one method per variant.
+///
+/// [`Expression::accept`] calls [`Visitor::visit_node`] on every node and then the
+/// method for its variant. Every method does nothing by default except visit the
+/// children in order, so an implementation only overrides the nodes it cares about
+/// and calls [`Expression::accept`] on the operands itself if it still wants to
+/// descend into them.
+pub trait Visitor {
+    /// Called on every node before the method for its variant.
+    fn visit_node(&mut self, _expr: &Expression) {}
+
+    fn visit_constant(&mut self, _value: f64) {}
+
+    #[cfg(feature = "rational")]
+    fn visit_rational(&mut self, _numerator: i64, _denominator: i64) {}
+
+    fn visit_pi(&mut self) {}
+
+    fn visit_e(&mut self) {}
+
+    fn visit_variable(&mut self) {}
+
+    fn visit_symbol(&mut self, _name: char) {}
+
+    fn visit_sum(&mut self, a: &Expression, b: &Expression) {
+        a.accept(self);
+        b.accept(self);
+    }
+
+    fn visit_difference(&mut self, a: &Expression, b: &Expression) {
+        a.accept(self);
+        b.accept(self);
+    }
+
+    fn visit_negate(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_product(&mut self, a: &Expression, b: &Expression) {
+        a.accept(self);
+        b.accept(self);
+    }
+
+    fn visit_quotient(&mut self, a: &Expression, b: &Expression) {
+        a.accept(self);
+        b.accept(self);
+    }
+
+    fn visit_reciprocal(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_power(&mut self, base: &Expression, _exponent: f64) {
+        base.accept(self);
+    }
+
+    fn visit_power_expr(&mut self, base: &Expression, exponent: &Expression) {
+        base.accept(self);
+        exponent.accept(self);
+    }
+
+    fn visit_root(&mut self, a: &Expression, _degree: f64) {
+        a.accept(self);
+    }
+
+    fn visit_sin(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_cos(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_tan(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_sec(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_csc(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_cot(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_atan(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_sinh(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_cosh(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_abs(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_sign(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_exp(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_ln(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_log(&mut self, _base: f64, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_derivative(&mut self, a: &Expression) {
+        a.accept(self);
+    }
+
+    fn visit_piecewise(&mut self, branches: &[(Condition, Rc<Expression>)]) {
+        for (_, branch) in branches {
+            branch.accept(self);
+        }
+    }
+}
+
+impl Expression {
+    /// Walks the expression with `visitor`, starting at this node.
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_node(self);
+        match self {
+            Expression::Constant(value) => visitor.visit_constant(*value),
+            #[cfg(feature = "rational")]
+            Expression::Rational(n, d) => visitor.visit_rational(*n, *d),
+            Expression::Pi => visitor.visit_pi(),
+            Expression::E => visitor.visit_e(),
+            Expression::Variable => visitor.visit_variable(),
+            Expression::Symbol(name) => visitor.visit_symbol(*name),
+            Expression::Sum(a, b) => visitor.visit_sum(a, b),
+            Expression::Difference(a, b) => visitor.visit_difference(a, b),
+            Expression::Negate(a) => visitor.visit_negate(a),
+            Expression::Product(a, b) => visitor.visit_product(a, b),
+            Expression::Quotient(a, b) => visitor.visit_quotient(a, b),
+            Expression::Reciprocal(a) => visitor.visit_reciprocal(a),
+            Expression::Power(base, exp) => visitor.visit_power(base, *exp),
+            Expression::PowerExpr(base, exp) => visitor.visit_power_expr(base, exp),
+            Expression::Root(a, n) => visitor.visit_root(a, *n),
+            Expression::Sin(a) => visitor.visit_sin(a),
+            Expression::Cos(a) => visitor.visit_cos(a),
+            Expression::Tan(a) => visitor.visit_tan(a),
+            Expression::Sec(a) => visitor.visit_sec(a),
+            Expression::Csc(a) => visitor.visit_csc(a),
+            Expression::Cot(a) => visitor.visit_cot(a),
+            Expression::Atan(a) => visitor.visit_atan(a),
+            Expression::Sinh(a) => visitor.visit_sinh(a),
+            Expression::Cosh(a) => visitor.visit_cosh(a),
+            Expression::Abs(a) => visitor.visit_abs(a),
+            Expression::Sign(a) => visitor.visit_sign(a),
+            Expression::Exp(a) => visitor.visit_exp(a),
+            Expression::Ln(a) => visitor.visit_ln(a),
+            Expression::Log(base, a) => visitor.visit_log(*base, a),
+            Expression::Derivative(a) => visitor.visit_derivative(a),
+            Expression::Piecewise(branches) => visitor.visit_piecewise(branches),
+        }
+    }
+}
+
+/// A [`Visitor`] counting the nodes it walks, which for a whole tree is
+/// [`Expression::node_count`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeCounter {
+    pub count: usize,
+}
+
+impl Visitor for NodeCounter {
+    fn visit_node(&mut self, _expr: &Expression) {
+        self.count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct ConstantSum {
+        total: f64,
+    }
+
+    impl Visitor for ConstantSum {
+        fn visit_constant(&mut self, value: f64) {
+            self.total += value;
+        }
+    }
+
+    #[test]
+    fn sums_constants() {
+        let expr = Expression::parse("2 * x^3 + sin(4 * x) - 1.5").unwrap();
+        let mut sum = ConstantSum::default();
+        expr.accept(&mut sum);
+        // The exponent of `x^3` is part of its power node rather than a constant.
+        assert_eq!(sum.total, 7.5);
+    }
+
+    #[test]
+    fn counts_nodes() {
+        let expr = Expression::parse("x^2 - 4").unwrap();
+        let mut counter = NodeCounter::default();
+        expr.accept(&mut counter);
+        assert_eq!(counter.count, expr.node_count());
+    }
+}
@@ -0,0 +1,51 @@
+use std::process::{Command, Output};
+
+fn lhopital(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lhopital"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn solves_a_known_limit() {
+    let output = lhopital(&["--num", "sin(x)", "--den", "x", "--at", "0"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Calculating limit of f(x) = (sin(x)) / (x) as x -> 0\n"));
+    assert!(stdout.contains("  Form: 0/0\n"));
+    assert!(stdout.ends_with("\nFinal Result: 1.0000\n"), "{}", stdout);
+}
+
+#[test]
+fn rejects_malformed_input() {
+    let output = lhopital(&["--num", "sin(x", "--den", "x", "--at", "0"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("Error: Unexpected end of input.\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn reports_limits_that_fail() {
+    let output = lhopital(&["--num", "x", "--den", "abs(x)", "--at", "0"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Error: Limit does not exist"), "{}", stderr);
+}
+
+#[test]
+fn names_a_recognized_constant() {
+    let output = lhopital(&["--num", "sin(x)", "--den", "2 * x", "--at", "0"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with("\nFinal Result: 0.5000 ≈ 1/2\n"),
+        "{}",
+        stdout
+    );
+}